@@ -1,71 +1,224 @@
 advent_of_code::solution!(1);
 
-fn parse_digit(line_bytes: &[u8], i: usize, part_one: bool) -> Option<u32> {
-    if line_bytes[i].is_ascii_digit() {
-        return Some((line_bytes[i] - b'0') as u32);
-    } else if !part_one {
-        if i + 5 <= line_bytes.len() {
-            match std::str::from_utf8(&line_bytes[i..(i + 5)]).unwrap() {
-                "three" => return Some(3),
-                "seven" => return Some(7),
-                "eight" => return Some(8),
-                _ => (),
+const DIGITS: [(&str, u32); 10] = [
+    ("0", 0),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const ENGLISH_WORDS: [(&str, u32); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+// Aho-Corasick automaton with a dense transition table, so every byte is a single lookup
+struct Automaton {
+    transitions: Vec<[usize; 256]>,
+    // Patterns ending at each state, including the ones reached through failure links
+    outputs: Vec<Vec<usize>>,
+    lengths: Vec<usize>,
+    max_len: usize,
+}
+
+impl Automaton {
+    fn new<'a>(patterns: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut transitions: Vec<[usize; 256]> = vec![[0; 256]];
+        let mut outputs: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut lengths = Vec::new();
+
+        // Trie, with 0 standing in for "no edge" since no edge leads back to the root
+        for (id, pattern) in patterns.into_iter().enumerate() {
+            let mut state = 0;
+            for &b in pattern {
+                if transitions[state][b as usize] == 0 {
+                    transitions.push([0; 256]);
+                    outputs.push(Vec::new());
+                    transitions[state][b as usize] = transitions.len() - 1;
+                }
+                state = transitions[state][b as usize];
             }
+            outputs[state].push(id);
+            lengths.push(pattern.len());
+        }
+
+        // Breadth-first over the trie, turning missing edges into failure transitions
+        let mut fail = vec![0; transitions.len()];
+        let mut queue: std::collections::VecDeque<usize> = transitions[0]
+            .iter()
+            .copied()
+            .filter(|&child| child != 0)
+            .collect();
+        while let Some(state) = queue.pop_front() {
+            let inherited = outputs[fail[state]].clone();
+            outputs[state].extend(inherited);
+
+            let fail_row = transitions[fail[state]];
+            for (child, fallback) in transitions[state].iter_mut().zip(fail_row) {
+                if *child == 0 {
+                    *child = fallback;
+                } else {
+                    fail[*child] = fallback;
+                    queue.push_back(*child);
+                }
+            }
+        }
+
+        Self {
+            transitions,
+            outputs,
+            max_len: lengths.iter().copied().max().unwrap_or(0),
+            lengths,
         }
-        if i + 4 <= line_bytes.len() {
-            match std::str::from_utf8(&line_bytes[i..(i + 4)]).unwrap() {
-                "four" => return Some(4),
-                "five" => return Some(5),
-                "nine" => return Some(9),
-                _ => (),
+    }
+
+    // Every match as (start, pattern id), in order of the end position
+    fn find_all(&self, text: impl IntoIterator<Item = u8>) -> Vec<(usize, usize)> {
+        let mut state = 0;
+        let mut matches = Vec::new();
+        for (i, b) in text.into_iter().enumerate() {
+            state = self.transitions[state][b as usize];
+            for &id in &self.outputs[state] {
+                matches.push((i + 1 - self.lengths[id], id));
             }
         }
-        if i + 3 <= line_bytes.len() {
-            match std::str::from_utf8(&line_bytes[i..(i + 3)]).unwrap() {
-                "one" => return Some(1),
-                "two" => return Some(2),
-                "six" => return Some(6),
-                _ => (),
+        matches
+    }
+
+    // Leftmost-starting match, preferring the longest pattern on ties. Scanning stops once no
+    // pattern could start early enough to beat the current best.
+    fn find_first(&self, text: impl IntoIterator<Item = u8>) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut best: Option<(usize, usize)> = None;
+        for (i, b) in text.into_iter().enumerate() {
+            if best.is_some_and(|(start, _)| i >= start + self.max_len) {
+                break;
+            }
+            state = self.transitions[state][b as usize];
+            for &id in &self.outputs[state] {
+                let start = i + 1 - self.lengths[id];
+                let better = best.is_none_or(|(best_start, best_id)| {
+                    start < best_start
+                        || (start == best_start && self.lengths[id] > self.lengths[best_id])
+                });
+                if better {
+                    best = Some((start, id));
+                }
             }
         }
+        best
     }
-    None
 }
 
-fn solve(input: &str, part_one: bool) -> Option<u32> {
-    Some(
+/// A dictionary word found in a line, spanning `start..start + word.len()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    start: usize,
+    word: &'a str,
+    value: u32,
+}
+
+/// Reads calibration values using an arbitrary word to number dictionary. A token spells out all
+/// digits of its value, so the calibration value is the leading digit of the first token followed
+/// by the trailing digit of the last one.
+struct Calibrator<'a> {
+    words: Vec<(&'a str, u32)>,
+    forward: Automaton,
+    // Built from the reversed words to scan lines from the end
+    backward: Automaton,
+}
+
+impl<'a> Calibrator<'a> {
+    fn new(dictionary: impl IntoIterator<Item = (&'a str, u32)>) -> Self {
+        let words: Vec<(&str, u32)> = dictionary.into_iter().collect();
+        let reversed: Vec<Vec<u8>> = words
+            .iter()
+            .map(|(word, _)| word.bytes().rev().collect())
+            .collect();
+
+        Self {
+            forward: Automaton::new(words.iter().map(|(word, _)| word.as_bytes())),
+            backward: Automaton::new(reversed.iter().map(|word| word.as_slice())),
+            words,
+        }
+    }
+
+    fn token(&self, start: usize, id: usize) -> Token<'a> {
+        let (word, value) = self.words[id];
+        Token { start, word, value }
+    }
+
+    /// Every (possibly overlapping) token in the line, ordered by where it ends.
+    fn tokens(&self, line: &str) -> Vec<Token<'a>> {
+        self.forward
+            .find_all(line.bytes())
+            .into_iter()
+            .map(|(start, id)| self.token(start, id))
+            .collect()
+    }
+
+    fn first_token(&self, line: &str) -> Option<Token<'a>> {
+        let (start, id) = self.forward.find_first(line.bytes())?;
+        Some(self.token(start, id))
+    }
+
+    fn last_token(&self, line: &str) -> Option<Token<'a>> {
+        let (rev_start, id) = self.backward.find_first(line.bytes().rev())?;
+        Some(self.token(line.len() - rev_start - self.words[id].0.len(), id))
+    }
+
+    fn calibrate(&self, line: &str) -> Option<u32> {
+        let first = self.first_token(line)?.value.to_string();
+        let last = self.last_token(line)?.value % 10;
+        Some((first.as_bytes()[0] - b'0') as u32 * 10 + last)
+    }
+
+    fn solve(&self, input: &str) -> u32 {
         input
             .lines()
-            .map(|line| {
-                let line = line.as_bytes();
-                let mut num = 0;
-
-                for i in 0..line.len() {
-                    if let Some(digit) = parse_digit(line, i, part_one) {
-                        num = digit * 10;
-                        break;
-                    }
-                }
-
-                for i in (0..line.len()).rev() {
-                    if let Some(digit) = parse_digit(line, i, part_one) {
-                        num += digit;
-                        break;
-                    }
-                }
+            .map(|line| self.calibrate(line).unwrap_or(0))
+            .sum()
+    }
+}
 
-                num
-            })
-            .sum(),
-    )
+/// Lists the tokens found on every line with the resulting calibration value, using the part two
+/// dictionary.
+pub fn explain(input: &str) -> String {
+    let calibrator = Calibrator::new(DIGITS.into_iter().chain(ENGLISH_WORDS));
+    input
+        .lines()
+        .map(|line| {
+            let tokens: Vec<String> = calibrator
+                .tokens(line)
+                .iter()
+                .map(|t| format!("{}@{}", t.word, t.start))
+                .collect();
+            let value = calibrator.calibrate(line).unwrap_or(0);
+            format!("{line}: [{}] => {value}", tokens.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    solve(input, true)
+    Some(Calibrator::new(DIGITS).solve(input))
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    solve(input, false)
+    Some(Calibrator::new(DIGITS.into_iter().chain(ENGLISH_WORDS)).solve(input))
 }
 
 #[cfg(test)]
@@ -87,4 +240,34 @@ mod tests {
         ));
         assert_eq!(result, Some(281));
     }
+
+    #[test]
+    fn test_tokens() {
+        let calibrator = Calibrator::new(DIGITS.into_iter().chain(ENGLISH_WORDS));
+        let words: Vec<&str> = calibrator
+            .tokens("xoneight3")
+            .iter()
+            .map(|t| t.word)
+            .collect();
+        assert_eq!(words, ["one", "eight", "3"]);
+        assert_eq!(calibrator.calibrate("oneight"), Some(18));
+        assert_eq!(calibrator.calibrate("abc"), None);
+        assert_eq!(
+            explain("xtwone3four"),
+            "xtwone3four: [two@1, one@3, 3@6, four@7] => 24"
+        );
+    }
+
+    #[test]
+    fn test_custom_dictionary() {
+        let german = [("eins", 1), ("zwei", 2), ("drei", 3), ("zwölf", 12)];
+        let calibrator = Calibrator::new(DIGITS.into_iter().chain(german));
+        assert_eq!(calibrator.calibrate("zweiundzwanzigdrei"), Some(23));
+        // multi-digit tokens contribute their leading and trailing digits
+        assert_eq!(calibrator.calibrate("zwölfxx"), Some(12));
+        assert_eq!(calibrator.calibrate("7zwölf"), Some(72));
+        // the longest word wins when several start at the same position
+        let overlapping = Calibrator::new([("one", 1), ("oneteen", 11)]);
+        assert_eq!(overlapping.calibrate("oneteen"), Some(11));
+    }
 }
@@ -1,96 +1,165 @@
 advent_of_code::solution!(2);
 
+use std::collections::{BTreeMap, BTreeSet};
+
 use nom::{
     bytes::complete::tag,
     character::complete::{self, alpha1, newline},
+    combinator::all_consuming,
     multi::separated_list1,
     sequence::separated_pair,
     IResult,
 };
 
-struct Round {
-    red: u32,
-    blue: u32,
-    green: u32,
-}
+/// Cube count per color, for a single draw or for the contents of a bag. Colors that are not
+/// listed count as zero.
+type Cubes<'a> = BTreeMap<&'a str, u32>;
 
-struct Game {
+struct Game<'a> {
     game_num: u32,
-    rounds: Vec<Round>,
+    rounds: Vec<Cubes<'a>>,
 }
 
-fn parse_round(input: &str) -> IResult<&str, Round> {
-    let (input, cubes) =
-        separated_list1(tag(", "), separated_pair(complete::u32, tag(" "), alpha1))(input)?;
+/// A draw that needs more cubes of one color than the bag holds.
+#[derive(Debug, PartialEq, Eq)]
+struct Violation<'a> {
+    round: usize,
+    color: &'a str,
+    drawn: u32,
+    available: u32,
+}
 
-    let mut round = Round {
-        red: 0,
-        blue: 0,
-        green: 0,
-    };
-    for c in &cubes {
-        match c.1 {
-            "red" => round.red = c.0,
-            "blue" => round.blue = c.0,
-            "green" => round.green = c.0,
-            _ => (),
-        }
+impl<'a> Game<'a> {
+    /// The fewest cubes of each color that make every round possible.
+    fn minimal_bag(&self) -> Cubes<'a> {
+        max_per_color(self.rounds.iter())
+    }
+
+    fn violations(&self, bag: &Cubes) -> Vec<Violation<'a>> {
+        self.rounds
+            .iter()
+            .enumerate()
+            .flat_map(|(round, cubes)| {
+                cubes.iter().filter_map(move |(&color, &drawn)| {
+                    let available = bag.get(color).copied().unwrap_or(0);
+                    (drawn > available).then_some(Violation {
+                        round,
+                        color,
+                        drawn,
+                        available,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    fn is_possible(&self, bag: &Cubes) -> bool {
+        self.violations(bag).is_empty()
     }
 
-    Ok((input, round))
+    /// Indices of the bags that this game could not have been played with.
+    fn impossible_bags(&self, bags: &[Cubes]) -> Vec<usize> {
+        (0..bags.len())
+            .filter(|&i| !self.is_possible(&bags[i]))
+            .collect()
+    }
+}
+
+fn max_per_color<'a, 'b: 'a>(rounds: impl Iterator<Item = &'a Cubes<'b>>) -> Cubes<'b> {
+    rounds.fold(Cubes::new(), |mut acc, cubes| {
+        for (&color, &count) in cubes {
+            let max = acc.entry(color).or_insert(0);
+            *max = (*max).max(count);
+        }
+        acc
+    })
+}
+
+fn colors<'a>(games: &[Game<'a>]) -> BTreeSet<&'a str> {
+    games
+        .iter()
+        .flat_map(|g| g.rounds.iter().flat_map(|r| r.keys().copied()))
+        .collect()
 }
 
-fn parse_game(input: &str) -> IResult<&str, Game> {
+fn parse_cubes(input: &str) -> IResult<&str, Cubes<'_>> {
+    let (input, cubes) =
+        separated_list1(tag(", "), separated_pair(complete::u32, tag(" "), alpha1))(input)?;
+
+    Ok((
+        input,
+        cubes
+            .into_iter()
+            .map(|(count, color)| (color, count))
+            .collect(),
+    ))
+}
+
+fn parse_game(input: &str) -> IResult<&str, Game<'_>> {
     let (input, _) = tag("Game ")(input)?;
     let (input, game_num) = complete::u32(input)?;
     let (input, _) = tag(": ")(input)?;
-    let (input, rounds) = separated_list1(tag("; "), parse_round)(input)?;
+    let (input, rounds) = separated_list1(tag("; "), parse_cubes)(input)?;
 
     Ok((input, Game { game_num, rounds }))
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Game>> {
+fn parse_input(input: &str) -> IResult<&str, Vec<Game<'_>>> {
     let (input, games) = separated_list1(newline, parse_game)(input)?;
 
     Ok((input, games))
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
+/// Parses a bag specification in the same format as a round, e.g. `12 red, 13 green, 14 blue`,
+/// or returns `None` if the whole specification is not in that format.
+fn parse_bag(spec: &str) -> Option<Cubes<'_>> {
+    let (_, bag) = all_consuming(parse_cubes)(spec).ok()?;
+    Some(bag)
+}
+
+/// For every game, its number and the indices of the bag specifications it could not have been
+/// played with, or `None` if a bag specification is malformed.
+pub fn impossible_bags(input: &str, bag_specs: &[&str]) -> Option<Vec<(u32, Vec<usize>)>> {
     let (_, games) = parse_input(input).unwrap();
-    let config = Round {
-        red: 12,
-        blue: 14,
-        green: 13,
-    };
+    let bags: Vec<Cubes> = bag_specs
+        .iter()
+        .map(|spec| parse_bag(spec))
+        .collect::<Option<_>>()?;
 
     Some(
         games
             .iter()
-            .map(|g| {
-                for r in &g.rounds {
-                    if r.red > config.red || r.blue > config.blue || r.green > config.green {
-                        return 0;
-                    }
-                }
+            .map(|g| (g.game_num, g.impossible_bags(&bags)))
+            .collect(),
+    )
+}
 
-                g.game_num
-            })
+pub fn part_one(input: &str) -> Option<u32> {
+    let (_, games) = parse_input(input).unwrap();
+    let bag = parse_bag("12 red, 13 green, 14 blue")?;
+
+    Some(
+        games
+            .iter()
+            .filter(|g| g.is_possible(&bag))
+            .map(|g| g.game_num)
             .sum(),
     )
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
     let (_, games) = parse_input(input).unwrap();
+    let colors = colors(&games);
 
     Some(
         games
             .iter()
             .map(|g| {
-                let max_red = g.rounds.iter().max_by_key(|r| r.red).unwrap().red;
-                let max_blue = g.rounds.iter().max_by_key(|r| r.blue).unwrap().blue;
-                let max_green = g.rounds.iter().max_by_key(|r| r.green).unwrap().green;
-
-                max_red * max_blue * max_green
+                let bag = g.minimal_bag();
+                colors
+                    .iter()
+                    .map(|c| bag.get(c).copied().unwrap_or(0))
+                    .product::<u32>()
             })
             .sum(),
     )
@@ -111,4 +180,76 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(2286));
     }
+
+    #[test]
+    fn test_bag_queries() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let (_, games) = parse_input(&input).unwrap();
+
+        assert_eq!(
+            colors(&games).into_iter().collect::<Vec<_>>(),
+            ["blue", "green", "red"]
+        );
+        assert_eq!(
+            max_per_color(games.iter().flat_map(|g| g.rounds.iter())),
+            parse_bag("20 red, 13 green, 15 blue").unwrap()
+        );
+        assert_eq!(
+            games[2].minimal_bag(),
+            parse_bag("20 red, 13 green, 6 blue").unwrap()
+        );
+
+        let bags = [
+            parse_bag("12 red, 13 green, 14 blue").unwrap(),
+            parse_bag("20 red, 13 green, 6 blue").unwrap(),
+            parse_bag("4 red, 2 green, 6 blue").unwrap(),
+        ];
+        assert_eq!(games[0].impossible_bags(&bags), Vec::<usize>::new());
+        assert_eq!(games[2].impossible_bags(&bags), [0, 2]);
+        assert_eq!(
+            impossible_bags(&input, &["12 red, 13 green, 14 blue"]),
+            Some(vec![
+                (1, vec![]),
+                (2, vec![]),
+                (3, vec![0]),
+                (4, vec![0]),
+                (5, vec![])
+            ])
+        );
+        assert_eq!(
+            games[2].violations(&bags[0]),
+            [Violation {
+                round: 0,
+                color: "red",
+                drawn: 20,
+                available: 12,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_custom_colors() {
+        let (_, games) =
+            parse_input("Game 1: 2 cyan, 1 magenta; 3 yellow\nGame 2: 5 cyan").unwrap();
+        let bag = parse_bag("4 cyan, 1 magenta, 3 yellow").unwrap();
+        assert!(games[0].is_possible(&bag));
+        assert!(!games[1].is_possible(&bag));
+        // colors missing from the bag hold no cubes
+        assert!(!games[0].is_possible(&parse_bag("4 cyan, 1 magenta").unwrap()));
+    }
+
+    #[test]
+    fn test_malformed_bags() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        for spec in [
+            "",
+            "12 red,",
+            "12 red, 13 green!",
+            "red 12",
+            "12 red; 13 green",
+        ] {
+            assert_eq!(parse_bag(spec), None, "{spec:?}");
+            assert_eq!(impossible_bags(&input, &["1 red", spec]), None, "{spec:?}");
+        }
+    }
 }
@@ -1,149 +1,163 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 advent_of_code::solution!(3);
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let map: Vec<&[u8]> = input.lines().map(|l| l.as_bytes()).collect();
+/// A number in the schematic, covering columns `start..end` of `row`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NumberSpan {
+    row: usize,
+    start: usize,
+    end: usize,
+    value: u32,
+}
 
-    Some(
-        map.iter()
-            .enumerate()
-            .map(|(i, row)| {
-                let mut part_num_row_sum = 0;
-
-                let mut num = 0;
-                let mut is_part_num = false;
-
-                for (j, c) in row.iter().enumerate() {
-                    if c.is_ascii_digit() {
-                        if !is_part_num {
-                            if i > 0 {
-                                if j > 0
-                                    && !map[i - 1][j - 1].is_ascii_digit()
-                                    && map[i - 1][j - 1] != b'.'
-                                {
-                                    is_part_num = true;
-                                } else if !map[i - 1][j].is_ascii_digit() && map[i - 1][j] != b'.' {
-                                    is_part_num = true;
-                                } else if j + 1 < row.len()
-                                    && !map[i - 1][j + 1].is_ascii_digit()
-                                    && map[i - 1][j + 1] != b'.'
-                                {
-                                    is_part_num = true;
-                                }
-                            }
-                            if i + 1 < row.len() {
-                                if j > 0
-                                    && !map[i + 1][j - 1].is_ascii_digit()
-                                    && map[i + 1][j - 1] != b'.'
-                                {
-                                    is_part_num = true;
-                                } else if !map[i + 1][j].is_ascii_digit() && map[i + 1][j] != b'.' {
-                                    is_part_num = true;
-                                } else if j + 1 < row.len()
-                                    && !map[i + 1][j + 1].is_ascii_digit()
-                                    && map[i + 1][j + 1] != b'.'
-                                {
-                                    is_part_num = true;
-                                }
-                            }
-                            if j > 0 && !row[j - 1].is_ascii_digit() && row[j - 1] != b'.' {
-                                is_part_num = true;
-                            }
-                            if j + 1 < row.len()
-                                && !row[j + 1].is_ascii_digit()
-                                && row[j + 1] != b'.'
-                            {
-                                is_part_num = true;
-                            }
-                        }
-                        num = num * 10 + (c - b'0') as u32;
-                    } else {
-                        if is_part_num {
-                            part_num_row_sum += num;
-                        }
-                        num = 0;
-                        is_part_num = false;
-                    }
-                }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Symbol {
+    row: usize,
+    col: usize,
+    kind: u8,
+}
 
-                if is_part_num {
-                    part_num_row_sum += num;
-                }
+/// Totals over every symbol of one kind.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct SymbolStats {
+    symbols: usize,
+    adjacent_numbers: usize,
+    adjacent_sum: u64,
+}
 
-                part_num_row_sum
-            })
-            .sum(),
-    )
+/// Numbers and symbols of an engine schematic, with the bipartite adjacency between them. Rows
+/// may have different lengths.
+struct Schematic {
+    numbers: Vec<NumberSpan>,
+    symbols: Vec<Symbol>,
+    // Indices into `symbols` for each number, and into `numbers` for each symbol
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let map: Vec<&[u8]> = input.lines().map(|l| l.as_bytes()).collect();
-    let mut gears: HashMap<(usize, usize), Vec<u32>> = HashMap::new();
-
-    for (i, row) in map.iter().enumerate() {
-        let mut num = 0;
-        let mut gear_coord: Option<(usize, usize)> = None;
-
-        for (j, c) in row.iter().enumerate() {
-            if c.is_ascii_digit() {
-                if gear_coord.is_none() {
-                    if i > 0 {
-                        if j > 0 && !map[i - 1][j - 1].is_ascii_digit() && map[i - 1][j - 1] == b'*'
-                        {
-                            gear_coord = Some((i - 1, j - 1));
-                        } else if !map[i - 1][j].is_ascii_digit() && map[i - 1][j] == b'*' {
-                            gear_coord = Some((i - 1, j));
-                        } else if j + 1 < row.len()
-                            && !map[i - 1][j + 1].is_ascii_digit()
-                            && map[i - 1][j + 1] == b'*'
-                        {
-                            gear_coord = Some((i - 1, j + 1));
-                        }
-                    }
-                    if i + 1 < row.len() {
-                        if j > 0 && !map[i + 1][j - 1].is_ascii_digit() && map[i + 1][j - 1] == b'*'
-                        {
-                            gear_coord = Some((i + 1, j - 1));
-                        } else if !map[i + 1][j].is_ascii_digit() && map[i + 1][j] == b'*' {
-                            gear_coord = Some((i + 1, j));
-                        } else if j + 1 < row.len()
-                            && !map[i + 1][j + 1].is_ascii_digit()
-                            && map[i + 1][j + 1] == b'*'
-                        {
-                            gear_coord = Some((i + 1, j + 1));
-                        }
-                    }
-                    if j > 0 && !row[j - 1].is_ascii_digit() && row[j - 1] == b'*' {
-                        gear_coord = Some((i, j - 1));
-                    }
-                    if j + 1 < row.len() && !row[j + 1].is_ascii_digit() && row[j + 1] == b'*' {
-                        gear_coord = Some((i, j + 1));
+impl Schematic {
+    fn new(input: &str) -> Self {
+        let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
+
+        for (row, line) in input.lines().enumerate() {
+            let line = line.as_bytes();
+            let mut col = 0;
+            while col < line.len() {
+                if line[col].is_ascii_digit() {
+                    let start = col;
+                    let mut value = 0;
+                    while col < line.len() && line[col].is_ascii_digit() {
+                        value = value * 10 + (line[col] - b'0') as u32;
+                        col += 1;
                     }
+                    numbers.push(NumberSpan {
+                        row,
+                        start,
+                        end: col,
+                        value,
+                    });
+                    continue;
                 }
-                num = num * 10 + (c - b'0') as u32;
-            } else {
-                if let Some(gear) = gear_coord {
-                    gears.entry(gear).or_insert(Vec::new()).push(num);
+                if line[col] != b'.' {
+                    symbols.push(Symbol {
+                        row,
+                        col,
+                        kind: line[col],
+                    });
+                }
+                col += 1;
+            }
+        }
+
+        let symbol_at: HashMap<(usize, usize), usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ((s.row, s.col), i))
+            .collect();
+
+        let mut number_symbols = vec![Vec::new(); numbers.len()];
+        let mut symbol_numbers = vec![Vec::new(); symbols.len()];
+        for (n, number) in numbers.iter().enumerate() {
+            for row in number.row.saturating_sub(1)..=number.row + 1 {
+                for col in number.start.saturating_sub(1)..=number.end {
+                    if let Some(&s) = symbol_at.get(&(row, col)) {
+                        number_symbols[n].push(s);
+                        symbol_numbers[s].push(n);
+                    }
                 }
-                num = 0;
-                gear_coord = None;
             }
         }
 
-        if let Some(gear) = gear_coord {
-            gears.entry(gear).or_insert(Vec::new()).push(num);
+        Self {
+            numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
         }
     }
 
-    let mut gear_ratio = 0;
-    for (_, nums) in &gears {
-        if nums.len() == 2 {
-            gear_ratio += nums.iter().product::<u32>();
+    /// Sum of every number next to at least one symbol.
+    fn part_numbers_sum(&self) -> u32 {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number.value)
+            .sum()
+    }
+
+    /// Product of the adjacent numbers for every `kind` symbol with exactly `neighbors` of them.
+    fn gear_ratios(&self, kind: u8, neighbors: usize) -> impl Iterator<Item = u64> + '_ {
+        self.symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(move |(symbol, numbers)| symbol.kind == kind && numbers.len() == neighbors)
+            .map(|(_, numbers)| {
+                numbers
+                    .iter()
+                    .map(|&n| self.numbers[n].value as u64)
+                    .product()
+            })
+    }
+
+    fn stats_by_kind(&self) -> BTreeMap<u8, SymbolStats> {
+        let mut stats: BTreeMap<u8, SymbolStats> = BTreeMap::new();
+        for (symbol, numbers) in self.symbols.iter().zip(&self.symbol_numbers) {
+            let entry = stats.entry(symbol.kind).or_default();
+            entry.symbols += 1;
+            entry.adjacent_numbers += numbers.len();
+            entry.adjacent_sum += numbers
+                .iter()
+                .map(|&n| self.numbers[n].value as u64)
+                .sum::<u64>();
         }
+        stats
     }
+}
+
+/// One line per symbol kind: how many there are, and the count and sum of their adjacent numbers.
+pub fn symbol_report(input: &str) -> String {
+    Schematic::new(input)
+        .stats_by_kind()
+        .iter()
+        .map(|(kind, stats)| {
+            format!(
+                "{}: {} symbols, {} adjacent numbers summing to {}",
+                *kind as char, stats.symbols, stats.adjacent_numbers, stats.adjacent_sum
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    Some(Schematic::new(input).part_numbers_sum())
+}
 
-    Some(gear_ratio)
+pub fn part_two(input: &str) -> Option<u64> {
+    Some(Schematic::new(input).gear_ratios(b'*', 2).sum())
 }
 
 #[cfg(test)]
@@ -161,4 +175,32 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(467835));
     }
+
+    #[test]
+    fn test_symbol_queries() {
+        let schematic = Schematic::new(&advent_of_code::template::read_file("examples", DAY));
+        // the lone `*` next to 617 only has one neighbor
+        assert_eq!(schematic.gear_ratios(b'*', 1).collect::<Vec<_>>(), [617]);
+        assert_eq!(
+            schematic.stats_by_kind()[&b'*'],
+            SymbolStats {
+                symbols: 3,
+                adjacent_numbers: 5,
+                adjacent_sum: 467 + 35 + 617 + 755 + 598,
+            }
+        );
+        assert_eq!(
+            symbol_report("1$2\n#.."),
+            "#: 1 symbols, 1 adjacent numbers summing to 1\n$: 1 symbols, 2 adjacent numbers summing to 3"
+        );
+    }
+
+    #[test]
+    fn test_non_square() {
+        // wider than tall, with a ragged last row
+        let schematic = Schematic::new("12.......5\n...*......\n7");
+        assert_eq!(schematic.part_numbers_sum(), 0);
+        let schematic = Schematic::new("..........\n.........*\n...4.....7\n.");
+        assert_eq!(schematic.part_numbers_sum(), 7);
+    }
 }
@@ -1,8 +1,7 @@
-use std::collections::HashSet;
-
 use nom::{
     bytes::complete::tag,
     character::complete::{self, digit1, newline, space1},
+    combinator::verify,
     multi::separated_list1,
     sequence::{preceded, separated_pair, tuple},
     IResult,
@@ -10,15 +9,74 @@ use nom::{
 
 advent_of_code::solution!(4);
 
+/// Set of card numbers as a bitmask; every number on a card is below 128.
+type NumberSet = u128;
+
 struct Card {
-    winning_nums: HashSet<u32>,
-    drawn_nums: HashSet<u32>,
-    copies: u32,
+    winning_nums: NumberSet,
+    drawn_nums: NumberSet,
+}
+
+impl Card {
+    fn matches(&self) -> usize {
+        (self.winning_nums & self.drawn_nums).count_ones() as usize
+    }
 }
 
-fn parse_nums(input: &str) -> IResult<&str, HashSet<u32>> {
-    let (input, nums) = separated_list1(space1, complete::u32)(input)?;
-    Ok((input, HashSet::from_iter(nums)))
+/// How winning cards hand out copies of the cards below them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CopyRule {
+    /// Following cards copied per matching number
+    cards_per_match: usize,
+    /// Copies of each following card won per instance of the winning card
+    multiplier: u64,
+    /// Drop copies of cards past the end of the table instead of rejecting the table
+    cap_at_end: bool,
+}
+
+const PUZZLE_RULE: CopyRule = CopyRule {
+    cards_per_match: 1,
+    multiplier: 1,
+    cap_at_end: true,
+};
+
+/// Number of instances of every card once all copies are won, or `None` if a card would copy a
+/// card past the end of the table and the rule does not allow it, or a card wins more copies
+/// than fit in a `u64`.
+///
+/// Each card adds its instance count to a contiguous run of following cards, so the additions are
+/// recorded where each run starts and ends and resolved with a running sum.
+fn cascade(cards: &[Card], rule: &CopyRule) -> Option<Vec<u64>> {
+    let mut starting = vec![0_u64; cards.len() + 1];
+    let mut ending = vec![0_u64; cards.len() + 1];
+    let mut copies = Vec::with_capacity(cards.len());
+    let mut running = 1_u64;
+
+    for (i, card) in cards.iter().enumerate() {
+        running = running.checked_add(starting[i])? - ending[i];
+        copies.push(running);
+
+        let mut end = i + 1 + card.matches() * rule.cards_per_match;
+        if end > cards.len() {
+            if !rule.cap_at_end {
+                return None;
+            }
+            end = cards.len();
+        }
+        let won = running.checked_mul(rule.multiplier)?;
+        starting[i + 1] = starting[i + 1].checked_add(won)?;
+        ending[end] += won;
+    }
+
+    Some(copies)
+}
+
+fn parse_nums(input: &str) -> IResult<&str, NumberSet> {
+    let (input, nums) = separated_list1(
+        space1,
+        verify(complete::u8, |&n| (n as u32) < NumberSet::BITS),
+    )(input)?;
+    Ok((input, nums.into_iter().fold(0, |set, n| set | 1 << n)))
 }
 
 fn parse_card(input: &str) -> IResult<&str, Card> {
@@ -32,7 +90,6 @@ fn parse_card(input: &str) -> IResult<&str, Card> {
         Card {
             winning_nums,
             drawn_nums,
-            copies: 1,
         },
     ))
 }
@@ -42,41 +99,40 @@ fn parse_input(input: &str) -> IResult<&str, Vec<Card>> {
     Ok((input, cards))
 }
 
+/// Instances of every card under a custom copy rule, see [`CopyRule`].
+pub fn copy_counts(
+    input: &str,
+    cards_per_match: usize,
+    multiplier: u64,
+    cap_at_end: bool,
+) -> Option<Vec<u64>> {
+    let (_, cards) = parse_input(input).unwrap();
+    let rule = CopyRule {
+        cards_per_match,
+        multiplier,
+        cap_at_end,
+    };
+    cascade(&cards, &rule)
+}
+
 pub fn part_one(input: &str) -> Option<u32> {
     let (_, cards) = parse_input(input).unwrap();
 
-    Some(
-        cards
-            .iter()
-            .map(|c| {
-                let num_matches = c.winning_nums.intersection(&c.drawn_nums).count() as u32;
-
-                if num_matches == 0 {
-                    0
-                } else {
-                    2_u32.pow(num_matches - 1)
-                }
-            })
-            .sum(),
-    )
+    // A card with more than 32 matches is worth more points than fit in a `u32`
+    cards.iter().try_fold(0_u32, |total, c| {
+        let points = match c.matches() {
+            0 => 0,
+            n => 1_u32.checked_shl(n as u32 - 1)?,
+        };
+        total.checked_add(points)
+    })
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let (_, mut cards) = parse_input(input).unwrap();
-
-    let mut num_scratchcards = 0;
-    for i in 0..cards.len() {
-        num_scratchcards += cards[i].copies;
-        let num_matches = cards[i]
-            .winning_nums
-            .intersection(&cards[i].drawn_nums)
-            .count();
-        for j in (i + 1)..(cards.len().min(i + 1 + num_matches)) {
-            cards[j].copies += cards[i].copies;
-        }
-    }
-
-    Some(num_scratchcards)
+pub fn part_two(input: &str) -> Option<u64> {
+    let (_, cards) = parse_input(input).unwrap();
+    cascade(&cards, &PUZZLE_RULE)?
+        .iter()
+        .try_fold(0_u64, |total, &copies| total.checked_add(copies))
 }
 
 #[cfg(test)]
@@ -94,4 +150,52 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(30));
     }
+
+    #[test]
+    fn test_copy_rules() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            copy_counts(&input, 1, 1, false),
+            Some(vec![1, 2, 4, 8, 14, 1])
+        );
+        // card 1 has four matches, so copying two cards per match runs off the table
+        assert_eq!(copy_counts(&input, 2, 1, false), None);
+        assert_eq!(
+            copy_counts(&input, 2, 1, true),
+            Some(vec![1, 2, 4, 8, 16, 16])
+        );
+        assert_eq!(
+            copy_counts(&input, 1, 2, true),
+            Some(vec![1, 3, 9, 27, 75, 1])
+        );
+        assert_eq!(copy_counts(&input, 1, u64::MAX, true), None);
+    }
+
+    #[test]
+    fn test_number_range() {
+        assert!(parse_card("Card 1: 127 | 127").is_ok());
+        assert!(parse_card("Card 1: 128 | 1").is_err());
+    }
+
+    #[test]
+    fn test_point_overflow() {
+        let card = |matches: u32| {
+            let nums = (1..=matches)
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("Card 1: {nums} | {nums}")
+        };
+        assert_eq!(part_one(&card(32)), Some(1 << 31));
+        assert_eq!(part_one(&card(33)), None);
+        assert_eq!(part_one(&format!("{}\n{}", card(32), card(32))), None);
+
+        // 64 cards that each copy all the cards after them up to the 64th hold 2^64 - 1
+        // instances between them, so one more card overflows the total
+        let mut cards: Vec<String> = (1..64).rev().map(card).collect();
+        cards.push("Card 1: 1 | 2".to_string());
+        assert_eq!(part_two(&cards.join("\n")), Some(u64::MAX));
+        cards.push("Card 1: 1 | 2".to_string());
+        assert_eq!(part_two(&cards.join("\n")), None);
+    }
 }
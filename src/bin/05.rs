@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use nom::{
     bytes::complete::tag,
     character::complete::{self, alpha1, newline, space1},
@@ -29,6 +31,147 @@ struct Almanac {
     maps: Vec<ConversionMap>,
 }
 
+/// A piece of a [`RangeMap`]: every value in `start..end` is shifted by `offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: i64,
+    end: i64,
+    offset: i64,
+}
+
+/// Piecewise-shift function over all of `i64`. Segments are sorted, contiguous and cover the whole
+/// line, with identity (`offset == 0`) segments filling the gaps between conversions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RangeMap {
+    segments: Vec<Segment>,
+}
+
+impl RangeMap {
+    fn identity() -> Self {
+        Self {
+            segments: vec![Segment {
+                start: i64::MIN,
+                end: i64::MAX,
+                offset: 0,
+            }],
+        }
+    }
+
+    // Fills the gaps between sorted, non-overlapping shifted segments and merges neighbors with the
+    // same offset
+    fn from_sorted(shifted: impl IntoIterator<Item = Segment>) -> Self {
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut push = |segment: Segment| {
+            if segment.start >= segment.end {
+                return;
+            }
+            match segments.last_mut() {
+                Some(last) if last.offset == segment.offset && last.end == segment.start => {
+                    last.end = segment.end;
+                }
+                _ => segments.push(segment),
+            }
+        };
+
+        let mut cursor = i64::MIN;
+        for segment in shifted {
+            push(Segment {
+                start: cursor,
+                end: segment.start,
+                offset: 0,
+            });
+            push(segment);
+            cursor = segment.end;
+        }
+        push(Segment {
+            start: cursor,
+            end: i64::MAX,
+            offset: 0,
+        });
+
+        Self { segments }
+    }
+
+    fn segment_of(&self, x: i64) -> &Segment {
+        let idx = self.segments.partition_point(|s| s.end <= x);
+        &self.segments[idx.min(self.segments.len() - 1)]
+    }
+
+    fn map(&self, x: i64) -> i64 {
+        x + self.segment_of(x).offset
+    }
+
+    /// Maps a whole interval, splitting it wherever it crosses a segment boundary. The returned
+    /// ranges are in the order of their preimages, not sorted by value.
+    fn map_range(&self, range: Range<i64>) -> Vec<Range<i64>> {
+        let first = self.segments.partition_point(|s| s.end <= range.start);
+        self.segments[first..]
+            .iter()
+            .take_while(|s| s.start < range.end)
+            .map(|s| {
+                let (start, end) = (s.start.max(range.start), s.end.min(range.end));
+                (start + s.offset)..(end + s.offset)
+            })
+            .collect()
+    }
+
+    /// Applies `self` first, then `next`.
+    fn then(&self, next: &RangeMap) -> RangeMap {
+        let shifted = self.segments.iter().flat_map(|s| {
+            let image = s.start.saturating_add(s.offset)..s.end.saturating_add(s.offset);
+            let first = next.segments.partition_point(|n| n.end <= image.start);
+            next.segments[first..]
+                .iter()
+                .take_while(move |n| n.start < image.end)
+                .map(move |n| Segment {
+                    start: n.start.max(image.start).saturating_sub(s.offset),
+                    end: n.end.min(image.end).saturating_sub(s.offset),
+                    offset: s.offset + n.offset,
+                })
+        });
+        RangeMap::from_sorted(shifted.filter(|s| s.offset != 0).collect::<Vec<_>>())
+    }
+
+    /// Every input that maps to `y`, in increasing order.
+    fn preimage(&self, y: i64) -> Vec<i64> {
+        let mut xs: Vec<i64> = self
+            .segments
+            .iter()
+            .filter_map(|s| {
+                let x = y.checked_sub(s.offset)?;
+                (s.start..s.end).contains(&x).then_some(x)
+            })
+            .collect();
+        xs.sort_unstable();
+        xs
+    }
+}
+
+impl From<&ConversionMap> for RangeMap {
+    fn from(map: &ConversionMap) -> Self {
+        let mut shifted: Vec<Segment> = map
+            .conversions
+            .iter()
+            .map(|c| Segment {
+                start: c.src_range_start,
+                end: c.src_range_start + c.range_length,
+                offset: c.dst_range_start - c.src_range_start,
+            })
+            .collect();
+        shifted.sort_unstable_by_key(|s| s.start);
+        RangeMap::from_sorted(shifted)
+    }
+}
+
+impl Almanac {
+    /// The single seed-to-location function of all maps chained together.
+    fn compose(&self) -> RangeMap {
+        self.maps
+            .iter()
+            .fold(RangeMap::identity(), |acc, m| acc.then(&RangeMap::from(m)))
+    }
+}
+
 fn parse_conversion(input: &str) -> IResult<&str, Conversion> {
     let (input, dst_range_start) = terminated(complete::i64, space1)(input)?;
     let (input, src_range_start) = terminated(complete::i64, space1)(input)?;
@@ -71,33 +214,36 @@ fn parse_input(input: &str) -> IResult<&str, Almanac> {
 }
 
 pub fn part_one(input: &str) -> Option<i64> {
-    let (_, mut almanac) = parse_input(input).unwrap();
-    let seeds = &mut almanac.seeds;
+    let (_, almanac) = parse_input(input).unwrap();
+    let location = almanac.compose();
 
-    Some(
-        *seeds
-            .iter_mut()
-            .map(|s| {
-                for m in &almanac.maps {
-                    for c in &m.conversions {
-                        if (c.src_range_start..(c.src_range_start + c.range_length)).contains(s) {
-                            *s += c.dst_range_start - c.src_range_start;
-                            break;
-                        }
-                    }
-                }
-                s
-            })
-            .min()
-            .unwrap(),
-    )
+    almanac.seeds.iter().map(|s| location.map(*s)).min()
 }
 
 pub fn part_two(input: &str) -> Option<i64> {
+    let (_, almanac) = parse_input(input).unwrap();
+    let location = almanac.compose();
+
+    almanac
+        .seeds
+        .chunks(2)
+        .flat_map(|s| location.map_range(s[0]..(s[0] + s[1])))
+        .map(|r| r.start)
+        .min()
+}
+
+/// Every seed number (planted or not) whose location is `location`.
+pub fn seeds_for_location(input: &str, location: i64) -> Vec<i64> {
+    let (_, almanac) = parse_input(input).unwrap();
+    almanac.compose().preimage(location)
+}
+
+// Reference variant that walks every seed through every map
+// Brute force - Part 2: 125742456 (4222.8s)
+pub fn part_two_brute_force(input: &str) -> Option<i64> {
     let (_, almanac) = parse_input(input).unwrap();
     let seeds = almanac.seeds.chunks(2).collect::<Vec<_>>();
 
-    // Brute force - Part 2: 125742456 (4222.8s)
     Some(
         seeds
             .iter()
@@ -143,4 +289,40 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(46));
     }
+
+    #[test]
+    fn test_part_two_brute_force() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(part_two_brute_force(&input), part_two(&input));
+    }
+
+    #[test]
+    fn test_range_map() {
+        let (_, almanac) =
+            parse_input(&advent_of_code::template::read_file("examples", DAY)).unwrap();
+        let location = almanac.compose();
+
+        // seed 82 -> location 46 from the puzzle walkthrough
+        assert_eq!(location.map(82), 46);
+        assert!(
+            seeds_for_location(&advent_of_code::template::read_file("examples", DAY), 46)
+                .contains(&82)
+        );
+        for seed in 0..100 {
+            let stepwise = almanac
+                .maps
+                .iter()
+                .fold(seed, |s, m| RangeMap::from(m).map(s));
+            assert_eq!(location.map(seed), stepwise);
+            assert!(location.preimage(stepwise).contains(&seed));
+        }
+        assert_eq!(
+            location
+                .map_range(79..93)
+                .iter()
+                .map(|r| r.end - r.start)
+                .sum::<i64>(),
+            14
+        );
+    }
 }
@@ -1,101 +1,161 @@
 use nom::{
     bytes::complete::tag,
-    character::complete::{self, digit1, newline, space1},
+    character::complete::{digit1, newline, space1},
     multi::separated_list1,
     sequence::tuple,
     IResult,
 };
+use num_bigint::BigUint;
+use num_integer::Roots;
+use num_traits::ToPrimitive;
 
 #[derive(Debug)]
 struct RaceRecord {
-    time: u64,
-    distance: u64,
+    time: BigUint,
+    distance: BigUint,
 }
 
 advent_of_code::solution!(6);
 
-fn parse_input_part_one(input: &str) -> IResult<&str, Vec<RaceRecord>> {
-    let (input, _) = tuple((tag("Time:"), space1))(input)?;
-    let (input, times) = separated_list1(space1, complete::u64)(input)?;
-    let (input, _) = tuple((newline, tag("Distance:"), space1))(input)?;
-    let (input, distances) = separated_list1(space1, complete::u64)(input)?;
+/// Number of whole hold times `h` with `h * (time - h) > distance`.
+///
+/// Winning hold times lie strictly between the roots `(time ± sqrt(time² - 4 * distance)) / 2`.
+/// With `s` the integer square root of the discriminant, `(time - s) / 2` rounded down is at most
+/// one below the first winning hold time, and the winning range is symmetric around `time / 2`.
+/// Hold times that exactly match the record do not win.
+fn ways_to_win<N: Roots + Clone>(time: N, distance: N) -> N {
+    let two = N::one() + N::one();
+    let square = time.clone() * time.clone();
+    let four_distance = distance.clone() * two.clone() * two.clone();
+    if square <= four_distance {
+        return N::zero();
+    }
 
-    Ok((
-        input,
-        times
-            .iter()
-            .zip(distances.iter())
-            .map(|(t, d)| RaceRecord {
-                time: *t,
-                distance: *d,
-            })
-            .collect(),
-    ))
+    let root = (square - four_distance).sqrt();
+    let mut hold = (time.clone() - root) / two.clone();
+    while hold.clone() * two.clone() <= time
+        && hold.clone() * (time.clone() - hold.clone()) <= distance
+    {
+        hold = hold + N::one();
+    }
+
+    if hold.clone() * two.clone() > time {
+        N::zero()
+    } else {
+        time - hold * two + N::one()
+    }
 }
 
-pub fn part_one(input: &str) -> Option<u64> {
-    let (_, races) = parse_input_part_one(input).unwrap();
+// Fallback for races that do not fit in a u64
+#[cfg(not(feature = "rug"))]
+fn ways_to_win_big(time: &BigUint, distance: &BigUint) -> BigUint {
+    ways_to_win(time.clone(), distance.clone())
+}
 
-    Some(
-        races
-            .iter()
-            .map(|r| {
-                let mut lose_count: u64 = 0;
-                for h in 0..=r.time {
-                    let distance = h * (r.time - h);
-                    if distance <= r.distance {
-                        lose_count += 1;
-                    }
-                }
-
-                r.time - lose_count + 1
-            })
-            .product(),
-    )
+// Same as `ways_to_win`, on GMP integers
+#[cfg(feature = "rug")]
+fn ways_to_win_big(time: &BigUint, distance: &BigUint) -> BigUint {
+    use rug::{integer::Order, Integer};
+
+    let time = Integer::from_digits(&time.to_u32_digits(), Order::Lsf);
+    let distance = Integer::from_digits(&distance.to_u32_digits(), Order::Lsf);
+    let discriminant = Integer::from(&time * &time) - Integer::from(&distance * 4);
+    if discriminant <= 0 {
+        return BigUint::default();
+    }
+
+    let mut hold = (&time - discriminant.sqrt()) / 2;
+    while Integer::from(&hold * 2) <= time && &hold * Integer::from(&time - &hold) <= distance {
+        hold += 1;
+    }
+
+    if Integer::from(&hold * 2) > time {
+        BigUint::default()
+    } else {
+        let ways: Integer = time - Integer::from(&hold * 2) + 1;
+        BigUint::new(ways.to_digits(Order::Lsf))
+    }
 }
 
-fn parse_input_part_two(input: &str) -> IResult<&str, RaceRecord> {
+impl RaceRecord {
+    fn ways_to_win(&self) -> BigUint {
+        // The discriminant of 64-bit races fits in a u128
+        if let (Some(time), Some(distance)) = (self.time.to_u64(), self.distance.to_u64()) {
+            return ways_to_win(time as u128, distance as u128).into();
+        }
+
+        ways_to_win_big(&self.time, &self.distance)
+    }
+
+    // Reference implementation trying every hold time, for races that fit in a u64
+    fn ways_to_win_brute_force(&self) -> u64 {
+        let time = self.time.to_u64().unwrap();
+        let distance = self.distance.to_u64().unwrap();
+        (0..=time).filter(|h| h * (time - h) > distance).count() as u64
+    }
+}
+
+fn parse_input(input: &str) -> IResult<&str, (Vec<&str>, Vec<&str>)> {
     let (input, _) = tuple((tag("Time:"), space1))(input)?;
     let (input, times) = separated_list1(space1, digit1)(input)?;
     let (input, _) = tuple((newline, tag("Distance:"), space1))(input)?;
     let (input, distances) = separated_list1(space1, digit1)(input)?;
 
-    let actual_time = times
+    Ok((input, (times, distances)))
+}
+
+fn parse_input_part_one(input: &str) -> Vec<RaceRecord> {
+    let (_, (times, distances)) = parse_input(input).unwrap();
+
+    times
         .iter()
-        .map(|t| t.chars())
-        .flatten()
-        .collect::<String>()
-        .parse::<u64>()
-        .unwrap();
-    let actual_distance = distances
+        .zip(distances.iter())
+        .map(|(t, d)| RaceRecord {
+            time: t.parse().unwrap(),
+            distance: d.parse().unwrap(),
+        })
+        .collect()
+}
+
+// The kerning is bad: all the numbers on a line make up a single, possibly huge, number
+fn parse_input_part_two(input: &str) -> RaceRecord {
+    let (_, (times, distances)) = parse_input(input).unwrap();
+
+    RaceRecord {
+        time: times.concat().parse().unwrap(),
+        distance: distances.concat().parse().unwrap(),
+    }
+}
+
+/// Ways to win the single race of part two, for inputs whose numbers do not fit in a `u64`.
+pub fn ways_to_win_concatenated(input: &str) -> BigUint {
+    parse_input_part_two(input).ways_to_win()
+}
+
+pub fn part_one(input: &str) -> Option<u64> {
+    parse_input_part_one(input)
         .iter()
-        .map(|d| d.chars())
-        .flatten()
-        .collect::<String>()
-        .parse::<u64>()
-        .unwrap();
-
-    Ok((
-        input,
-        RaceRecord {
-            time: actual_time,
-            distance: actual_distance,
-        },
-    ))
+        .map(|r| r.ways_to_win().to_u64())
+        .product()
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let (_, race) = parse_input_part_two(input).unwrap();
+    parse_input_part_two(input).ways_to_win().to_u64()
+}
 
-    let mut lose_count: u64 = 0;
-    for h in 0..=race.time {
-        let distance = h * (race.time - h);
-        if distance <= race.distance {
-            lose_count += 1;
-        }
-    }
+/// Part one, trying every hold time.
+pub fn part_one_brute_force(input: &str) -> Option<u64> {
+    Some(
+        parse_input_part_one(input)
+            .iter()
+            .map(RaceRecord::ways_to_win_brute_force)
+            .product(),
+    )
+}
 
-    Some(race.time - lose_count + 1)
+/// Part two, trying every hold time.
+pub fn part_two_brute_force(input: &str) -> Option<u64> {
+    Some(parse_input_part_two(input).ways_to_win_brute_force())
 }
 
 #[cfg(test)]
@@ -113,4 +173,73 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(71503));
     }
+
+    #[test]
+    fn test_brute_force() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(part_one_brute_force(&input), part_one(&input));
+        assert_eq!(part_two_brute_force(&input), part_two(&input));
+
+        for time in 0..60_u64 {
+            for distance in 0..(time * time / 4 + 2) {
+                let race = RaceRecord {
+                    time: time.into(),
+                    distance: distance.into(),
+                };
+                assert_eq!(
+                    race.ways_to_win(),
+                    race.ways_to_win_brute_force().into(),
+                    "time {time}, distance {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_boundary_ties() {
+        // holding for 5 exactly matches the record
+        assert_eq!(ways_to_win(10_u128, 25), 0);
+        assert_eq!(ways_to_win(10_u128, 24), 1);
+        // the roots 1 and 2 are whole numbers, so nothing lies strictly between them
+        assert_eq!(ways_to_win(3_u128, 2), 0);
+        assert_eq!(ways_to_win(u64::MAX as u128, 0), u64::MAX as u128 - 1);
+    }
+
+    #[test]
+    fn test_long_race() {
+        // the discriminant no longer fits in a u128
+        let input = "Time:      1000000000000 000000000000000000 00000\nDistance:  1 0";
+        let time = BigUint::from(10_u32).pow(35);
+        assert_eq!(ways_to_win_concatenated(input), time.clone() - 1_u32);
+        assert_eq!(
+            ways_to_win(time.clone(), time.clone() * time.clone() / 4_u32),
+            BigUint::default()
+        );
+        assert_eq!(part_two(input), None);
+    }
+
+    #[cfg(feature = "rug")]
+    #[test]
+    fn test_rug_fallback() {
+        for time in 0..60_u64 {
+            for distance in 0..(time * time / 4 + 2) {
+                let race = RaceRecord {
+                    time: time.into(),
+                    distance: distance.into(),
+                };
+                let ways = ways_to_win_big(&race.time, &race.distance);
+                assert_eq!(ways, race.ways_to_win_brute_force().into());
+                assert_eq!(ways, ways_to_win(time as u128, distance as u128).into());
+            }
+        }
+
+        let input = "Time:      1000000000000 000000000000000000 0000000000\n\
+                     Distance:  123456789012345 678901234567890 123456789012345678";
+        let race = parse_input_part_two(input);
+        assert!(race.time.bits() > 128);
+        assert_eq!(
+            ways_to_win_big(&race.time, &race.distance),
+            ways_to_win(race.time.clone(), race.distance.clone())
+        );
+    }
 }
@@ -1,5 +1,3 @@
-use std::collections::BTreeMap;
-
 use nom::{
     character::complete::{self, alphanumeric1, newline, space1},
     multi::separated_list1,
@@ -9,187 +7,206 @@ use nom::{
 
 advent_of_code::solution!(7);
 
-// Implicitly orders from top as least to bottom as greatest
-#[derive(Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
-enum Card {
-    JPartTwo,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    T,
-    JPartOne,
-    Q,
-    K,
-    A,
+/// A kind of hand, matched by any hand whose groups of equal cards are at least as large as the
+/// group sizes in `pattern`, taken largest first.
+#[derive(Debug, PartialEq, Eq)]
+struct HandType {
+    name: &'static str,
+    pattern: &'static [usize],
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
-enum HandType {
-    HighCard,
-    OnePair,
-    TwoPair,
-    ThreeOfKind,
-    FullHouse,
-    FourOfKind,
-    FiveOfKind,
-}
+// Weakest to strongest
+const STANDARD_TYPES: [HandType; 7] = [
+    HandType {
+        name: "high card",
+        pattern: &[],
+    },
+    HandType {
+        name: "one pair",
+        pattern: &[2],
+    },
+    HandType {
+        name: "two pair",
+        pattern: &[2, 2],
+    },
+    HandType {
+        name: "three of a kind",
+        pattern: &[3],
+    },
+    HandType {
+        name: "full house",
+        pattern: &[3, 2],
+    },
+    HandType {
+        name: "four of a kind",
+        pattern: &[4],
+    },
+    HandType {
+        name: "five of a kind",
+        pattern: &[5],
+    },
+];
 
-#[derive(Debug)]
-struct Hand {
-    cards: Vec<Card>,
-    bid: u32,
-    hand_type: Option<HandType>,
+/// Rules for ranking hands: hands compare by type first, then card by card.
+struct RuleSet<'a> {
+    /// Card labels from weakest to strongest
+    order: &'a str,
+    /// Labels that stand in for whatever card makes the strongest type
+    wildcards: &'a str,
+    /// Hand types from weakest to strongest
+    types: &'a [HandType],
 }
 
-fn parse_hand_part_one(input: &str) -> IResult<&str, Hand> {
-    let (input, (cards_raw, bid)) = separated_pair(alphanumeric1, space1, complete::u32)(input)?;
-    let cards: Vec<Card> = cards_raw
-        .as_bytes()
-        .iter()
-        .map(|c| match c {
-            b'A' => Card::A,
-            b'K' => Card::K,
-            b'Q' => Card::Q,
-            b'J' => Card::JPartOne,
-            b'T' => Card::T,
-            b'9' => Card::Nine,
-            b'8' => Card::Eight,
-            b'7' => Card::Seven,
-            b'6' => Card::Six,
-            b'5' => Card::Five,
-            b'4' => Card::Four,
-            b'3' => Card::Three,
-            b'2' => Card::Two,
-            _ => unreachable!(),
-        })
-        .collect();
-
-    Ok((
-        input,
-        Hand {
-            cards,
-            bid,
-            hand_type: None,
-        },
-    ))
-}
+const PART_ONE: RuleSet = RuleSet {
+    order: "23456789TJQKA",
+    wildcards: "",
+    types: &STANDARD_TYPES,
+};
 
-fn parse_hand_part_two(input: &str) -> IResult<&str, Hand> {
-    let (input, (cards_raw, bid)) = separated_pair(alphanumeric1, space1, complete::u32)(input)?;
-    let cards: Vec<Card> = cards_raw
-        .as_bytes()
-        .iter()
-        .map(|c| match c {
-            b'A' => Card::A,
-            b'K' => Card::K,
-            b'Q' => Card::Q,
-            b'J' => Card::JPartTwo,
-            b'T' => Card::T,
-            b'9' => Card::Nine,
-            b'8' => Card::Eight,
-            b'7' => Card::Seven,
-            b'6' => Card::Six,
-            b'5' => Card::Five,
-            b'4' => Card::Four,
-            b'3' => Card::Three,
-            b'2' => Card::Two,
-            _ => unreachable!(),
-        })
-        .collect();
-
-    Ok((
-        input,
-        Hand {
-            cards,
-            bid,
-            hand_type: None,
-        },
-    ))
-}
+const PART_TWO: RuleSet = RuleSet {
+    order: "J23456789TQKA",
+    wildcards: "J",
+    types: &STANDARD_TYPES,
+};
 
-fn parse_input(input: &str, part_one: bool) -> IResult<&str, Vec<Hand>> {
-    let (input, hands) = separated_list1(
-        newline,
-        if part_one {
-            parse_hand_part_one
-        } else {
-            parse_hand_part_two
-        },
-    )(input)?;
-    Ok((input, hands))
+/// How a hand makes up one group of its type: `count` natural cards of `label` (if any) plus
+/// `wild` wildcards.
+#[derive(Debug, PartialEq, Eq)]
+struct Slot {
+    label: Option<char>,
+    count: usize,
+    wild: usize,
 }
 
-fn solve(input: &str, part_one: bool) -> u32 {
-    let (_, mut hands) = parse_input(input, part_one).unwrap();
-
-    for h in hands.iter_mut() {
-        let mut card_freq: Vec<u8> = if part_one {
-            h.cards
-                .iter()
-                .fold(BTreeMap::new(), |mut freq, c| {
-                    *freq.entry(c).or_default() += 1;
-                    freq
-                })
-                .into_values()
-                .collect()
-        } else {
-            let mut card_freq_map: BTreeMap<&Card, u8> =
-                h.cards.iter().fold(BTreeMap::new(), |mut freq, c| {
-                    *freq.entry(c).or_default() += 1;
-                    freq
+impl RuleSet<'_> {
+    fn strength(&self, card: char) -> usize {
+        self.order
+            .find(card)
+            .unwrap_or_else(|| panic!("unknown card {card}"))
+    }
+
+    /// Groups of equal natural cards, largest first and stronger cards first among equal sizes,
+    /// and the number of wildcards.
+    fn groups(&self, cards: &str) -> (Vec<(char, usize)>, usize) {
+        let mut groups: Vec<(char, usize)> = Vec::new();
+        let mut wild = 0;
+        for card in cards.chars() {
+            if self.wildcards.contains(card) {
+                wild += 1;
+            } else if let Some(group) = groups.iter_mut().find(|(label, _)| *label == card) {
+                group.1 += 1;
+            } else {
+                groups.push((card, 1));
+            }
+        }
+        groups.sort_by_key(|&(label, count)| std::cmp::Reverse((count, self.strength(label))));
+        (groups, wild)
+    }
+
+    /// The strongest type the hand can make, with the group filling each slot of its pattern.
+    ///
+    /// Pairing the largest groups with the largest slots needs the fewest wildcards, so a type is
+    /// reachable exactly when that pairing leaves no slot short.
+    fn classify(&self, cards: &str) -> (usize, Vec<Slot>) {
+        let (groups, wild) = self.groups(cards);
+
+        for (index, hand_type) in self.types.iter().enumerate().rev() {
+            let mut remaining = wild;
+            let mut slots = Vec::new();
+            for (i, &size) in hand_type.pattern.iter().enumerate() {
+                let (label, count) = match groups.get(i) {
+                    Some(&(label, count)) => (Some(label), count.min(size)),
+                    None => (None, 0),
+                };
+                let needed = size - count;
+                if needed > remaining {
+                    break;
+                }
+                remaining -= needed;
+                slots.push(Slot {
+                    label,
+                    count,
+                    wild: needed,
                 });
-            if card_freq_map.len() != 1 {
-                let joker_count = card_freq_map.remove(&Card::JPartTwo).unwrap_or_default();
-                card_freq_map
-                    .iter_mut()
-                    .max_by(|(_, x), (_, y)| x.cmp(y))
-                    .map(|(_, v)| *v += joker_count);
             }
+            if slots.len() == hand_type.pattern.len() {
+                return (index, slots);
+            }
+        }
+        panic!("no hand type matches {cards}")
+    }
 
-            card_freq_map.into_values().collect()
-        };
-        card_freq.sort();
-        card_freq.reverse();
-
-        if card_freq.len() == 1 {
-            h.hand_type = Some(HandType::FiveOfKind);
-        } else if card_freq.len() == 2 && card_freq[0] == 4 && card_freq[1] == 1 {
-            h.hand_type = Some(HandType::FourOfKind);
-        } else if card_freq.len() == 2 && card_freq[0] == 3 && card_freq[1] == 2 {
-            h.hand_type = Some(HandType::FullHouse);
-        } else if card_freq.len() == 3 && card_freq[0] == 3 && card_freq[1] == 1 {
-            h.hand_type = Some(HandType::ThreeOfKind);
-        } else if card_freq.len() == 3 && card_freq[0] == 2 && card_freq[1] == 2 {
-            h.hand_type = Some(HandType::TwoPair);
-        } else if card_freq.len() == 4 {
-            h.hand_type = Some(HandType::OnePair);
+    fn explain(&self, cards: &str) -> String {
+        let (index, slots) = self.classify(cards);
+        let slots: Vec<String> = slots
+            .iter()
+            .map(|slot| match (slot.label, slot.wild) {
+                (Some(label), 0) => format!("{label}×{}", slot.count),
+                (Some(label), wild) => format!("{label}×{}+{wild} wild", slot.count),
+                (None, wild) => format!("{wild} wild"),
+            })
+            .collect();
+
+        let name = self.types[index].name;
+        if slots.is_empty() {
+            format!("{cards}: {name}")
         } else {
-            h.hand_type = Some(HandType::HighCard);
+            format!("{cards}: {name} ({})", slots.join(", "))
         }
     }
 
-    hands.sort_by(|a, b| {
-        (a.hand_type.as_ref().unwrap(), &a.cards).cmp(&(b.hand_type.as_ref().unwrap(), &b.cards))
-    });
+    fn total_winnings(&self, hands: &[Hand]) -> u32 {
+        let mut ranked: Vec<((usize, Vec<usize>), u32)> = hands
+            .iter()
+            .map(|h| {
+                let strengths = h.cards.chars().map(|c| self.strength(c)).collect();
+                ((self.classify(h.cards).0, strengths), h.bid)
+            })
+            .collect();
+        ranked.sort();
+
+        ranked
+            .iter()
+            .enumerate()
+            .map(|(i, (_, bid))| bid * (i as u32 + 1))
+            .sum()
+    }
+}
+
+#[derive(Debug)]
+struct Hand<'a> {
+    cards: &'a str,
+    bid: u32,
+}
+
+fn parse_hand(input: &str) -> IResult<&str, Hand<'_>> {
+    let (input, (cards, bid)) = separated_pair(alphanumeric1, space1, complete::u32)(input)?;
+    Ok((input, Hand { cards, bid }))
+}
+
+fn parse_input(input: &str) -> IResult<&str, Vec<Hand<'_>>> {
+    let (input, hands) = separated_list1(newline, parse_hand)(input)?;
+    Ok((input, hands))
+}
 
+/// The type of every hand under the part two rules, and the groups that make it up.
+pub fn explain(input: &str) -> String {
+    let (_, hands) = parse_input(input).unwrap();
     hands
         .iter()
-        .enumerate()
-        .map(|(i, h)| h.bid * (i as u32 + 1))
-        .sum()
+        .map(|h| PART_TWO.explain(h.cards))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    Some(solve(input, true))
+    let (_, hands) = parse_input(input).unwrap();
+    Some(PART_ONE.total_winnings(&hands))
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    Some(solve(input, false))
+    let (_, hands) = parse_input(input).unwrap();
+    Some(PART_TWO.total_winnings(&hands))
 }
 
 #[cfg(test)]
@@ -207,4 +224,75 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(5905));
     }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(
+            explain(&advent_of_code::template::read_file("examples", DAY)),
+            "32T3K: one pair (3×2)\n\
+             T55J5: four of a kind (5×3+1 wild)\n\
+             KK677: two pair (K×2, 7×2)\n\
+             KTJJT: four of a kind (T×2+2 wild)\n\
+             QQQJA: four of a kind (Q×3+1 wild)"
+        );
+        assert_eq!(PART_TWO.explain("JJJJJ"), "JJJJJ: five of a kind (5 wild)");
+        assert_eq!(PART_ONE.explain("23456"), "23456: high card");
+        assert_eq!(PART_ONE.explain("KTJJT"), "KTJJT: two pair (J×2, T×2)");
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let types = [
+            HandType {
+                name: "high card",
+                pattern: &[],
+            },
+            HandType {
+                name: "one pair",
+                pattern: &[2],
+            },
+            HandType {
+                name: "two triples",
+                pattern: &[3, 3],
+            },
+            HandType {
+                name: "six of a kind",
+                pattern: &[6],
+            },
+        ];
+        let rules = RuleSet {
+            order: "JQ23456789TKA",
+            wildcards: "JQ",
+            types: &types,
+        };
+        assert_eq!(rules.explain("2J3Q45"), "2J3Q45: one pair (5×1+1 wild)");
+        assert_eq!(
+            rules.explain("22J33Q"),
+            "22J33Q: two triples (3×2+1 wild, 2×2+1 wild)"
+        );
+        assert_eq!(
+            rules.explain("QQJJA2"),
+            "QQJJA2: two triples (A×1+2 wild, 2×1+2 wild)"
+        );
+        assert_eq!(
+            rules.explain("QQJJAA"),
+            "QQJJAA: six of a kind (A×2+4 wild)"
+        );
+
+        let hands = [
+            Hand {
+                cards: "QQJJAA",
+                bid: 1,
+            },
+            Hand {
+                cards: "222333",
+                bid: 10,
+            },
+            Hand {
+                cards: "223456",
+                bid: 100,
+            },
+        ];
+        assert_eq!(rules.total_winnings(&hands), 100 + 10 * 2 + 3);
+    }
 }
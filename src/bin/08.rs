@@ -1,125 +1,222 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
+use advent_of_code::cycle::{self, Arrivals};
 use nom::{
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1},
-    multi::fold_many1,
-    sequence::{delimited, separated_pair, terminated},
+    character::complete::{alpha1, alphanumeric1, newline},
+    multi::separated_list1,
+    sequence::{delimited, separated_pair, terminated, tuple},
     IResult,
 };
 
 advent_of_code::solution!(8);
 
-struct Branch {
-    start: (u8, u8, u8),
-    left: (u8, u8, u8),
-    right: (u8, u8, u8),
+struct Branch<'a> {
+    start: &'a str,
+    left: &'a str,
+    right: &'a str,
 }
 
-fn parse_branch(input: &str) -> IResult<&str, Branch> {
+/// The node network with nodes numbered by their position in the input.
+struct Network<'a> {
+    // Whether each instruction turns right
+    instructions: Vec<bool>,
+    names: Vec<&'a str>,
+    // Left and right successor of every node
+    next: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum WalkError {
+    UnknownNode(String),
+    NoStart,
+    /// The ghost starting at this node never stands on a goal node.
+    NoGoal(String),
+    /// The ghost starting at this node never arrives together with the ghosts before it.
+    NeverTogether {
+        ghost: String,
+        arrivals: Box<Arrivals>,
+        others: Box<Arrivals>,
+    },
+}
+
+impl Display for WalkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalkError::UnknownNode(node) => write!(f, "unknown node {node}"),
+            WalkError::NoStart => f.write_str("no ghost starts anywhere"),
+            WalkError::NoGoal(ghost) => write!(f, "ghost from {ghost} never reaches a goal"),
+            WalkError::NeverTogether {
+                ghost,
+                arrivals,
+                others,
+            } => write!(
+                f,
+                "ghost from {ghost} arrives at {arrivals}, never at the same time as the ghosts \
+                 before it, which arrive at {others}"
+            ),
+        }
+    }
+}
+
+impl<'a> Network<'a> {
+    fn new(path: &str, branches: &[Branch<'a>]) -> Result<Self, WalkError> {
+        let names: Vec<&str> = branches.iter().map(|b| b.start).collect();
+        let positions: HashMap<&str, usize> =
+            names.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let index = |name: &str| {
+            positions
+                .get(name)
+                .copied()
+                .ok_or_else(|| WalkError::UnknownNode(name.to_string()))
+        };
+        let next = branches
+            .iter()
+            .map(|b| Ok([index(b.left)?, index(b.right)?]))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            instructions: path.bytes().map(|b| b == b'R').collect(),
+            next,
+            names,
+        })
+    }
+
+    fn step(&self, &(node, i): &(usize, usize)) -> (usize, usize) {
+        (
+            self.next[node][self.instructions[i] as usize],
+            (i + 1) % self.instructions.len(),
+        )
+    }
+
+    /// Walks the (node, instruction index) states from `start` until they repeat, noting every
+    /// step that lands on a goal.
+    fn arrivals(&self, start: usize, is_goal: impl Fn(&str) -> bool) -> Arrivals {
+        let cycle = cycle::hashed(&(start, 0), |s| self.step(s));
+
+        let mut once = Vec::new();
+        let mut residues = Vec::new();
+        let mut state = (start, 0);
+        for step in 0..cycle.prefix + cycle.period {
+            if is_goal(self.names[state.0]) {
+                if step < cycle.prefix {
+                    once.push(step as u64);
+                } else {
+                    residues.push(step as u64);
+                }
+            }
+            state = self.step(&state);
+        }
+
+        Arrivals {
+            once,
+            offset: cycle.prefix as u64,
+            period: cycle.period as u64,
+            residues,
+        }
+    }
+
+    /// Arrivals of the ghost from every start node.
+    fn ghosts(
+        &self,
+        is_start: impl Fn(&str) -> bool,
+        is_goal: impl Fn(&str) -> bool,
+    ) -> Vec<(&'a str, Arrivals)> {
+        (0..self.names.len())
+            .filter(|&n| is_start(self.names[n]))
+            .map(|n| (self.names[n], self.arrivals(n, &is_goal)))
+            .collect()
+    }
+
+    /// The first step at which every ghost stands on a goal node at once.
+    fn first_arrival(
+        &self,
+        is_start: impl Fn(&str) -> bool,
+        is_goal: impl Fn(&str) -> bool,
+    ) -> Result<u64, WalkError> {
+        let mut together: Option<Arrivals> = None;
+        for (ghost, arrivals) in self.ghosts(is_start, is_goal) {
+            if arrivals.is_empty() {
+                return Err(WalkError::NoGoal(ghost.to_string()));
+            }
+            let combined = match &together {
+                Some(others) => others.intersect(&arrivals),
+                None => arrivals.clone(),
+            };
+            if combined.first().is_none() {
+                return Err(WalkError::NeverTogether {
+                    ghost: ghost.to_string(),
+                    arrivals: Box::new(arrivals),
+                    others: Box::new(together.unwrap_or(combined)),
+                });
+            }
+            together = Some(combined);
+        }
+
+        together.and_then(|a| a.first()).ok_or(WalkError::NoStart)
+    }
+}
+
+fn parse_branch(input: &str) -> IResult<&str, Branch<'_>> {
     let (input, (start, (left, right))) = separated_pair(
         alphanumeric1,
         tag(" = "),
         delimited(
             tag("("),
             separated_pair(alphanumeric1, tag(", "), alphanumeric1),
-            tag(")\n"),
+            tag(")"),
         ),
     )(input)?;
 
-    let start_arr = start.as_bytes();
-    let left_arr = left.as_bytes();
-    let right_arr = right.as_bytes();
-
-    Ok((
-        input,
-        Branch {
-            start: (start_arr[0], start_arr[1], start_arr[2]),
-            left: (left_arr[0], left_arr[1], left_arr[2]),
-            right: (right_arr[0], right_arr[1], right_arr[2]),
-        },
-    ))
+    Ok((input, Branch { start, left, right }))
 }
 
-fn parse_input(input: &str) -> IResult<&str, (&[u8], HashMap<(u8, u8, u8), Branch>)> {
-    let (input, path_str) = terminated(alpha1, tag("\n\n"))(input)?;
-    let (input, branches) = fold_many1(parse_branch, HashMap::new, |mut acc, b| {
-        acc.insert(b.start.clone(), b);
-        acc
-    })(input)?;
+fn parse_input(input: &str) -> IResult<&str, (&str, Vec<Branch<'_>>)> {
+    let (input, path) = terminated(alpha1, tuple((newline, newline)))(input)?;
+    let (input, branches) = separated_list1(newline, parse_branch)(input)?;
 
-    Ok((input, (path_str.as_bytes(), branches)))
+    Ok((input, (path, branches)))
 }
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, (path, map)) = parse_input(input).unwrap();
-
-    let mut steps = 0;
-    let mut position = (b'A', b'A', b'A');
-
-    while position != (b'Z', b'Z', b'Z') {
-        position = if path[steps % path.len()] == b'L' {
-            map.get(&position).unwrap().left
-        } else {
-            map.get(&position).unwrap().right
-        };
-
-        steps += 1;
-    }
 
-    Some(steps as u32)
+fn parse_network(input: &str) -> Result<Network<'_>, WalkError> {
+    let (_, (path, branches)) = parse_input(input).unwrap();
+    Network::new(path, &branches)
 }
 
-fn gcd(mut a: u64, mut b: u64) -> u64 {
-    while b != 0 {
-        let temp = b;
-        b = a % b;
-        a = temp;
-    }
-
-    a
+fn is_part_two_start(name: &str) -> bool {
+    name.ends_with('A')
 }
 
-fn lcm(a: u64, b: u64) -> u64 {
-    a * b / gcd(a, b)
+fn is_part_two_goal(name: &str) -> bool {
+    name.ends_with('Z')
 }
 
-pub fn part_two(input: &str) -> Option<u64> {
-    let (_, (path, map)) = parse_input(input).unwrap();
-
-    let mut position_cycles: Vec<(&(u8, u8, u8), Vec<(&(u8, u8, u8), usize)>)> = map
+/// When every part two ghost reaches a goal node.
+pub fn ghost_report(input: &str) -> String {
+    let network = parse_network(input).unwrap();
+    network
+        .ghosts(is_part_two_start, is_part_two_goal)
         .iter()
-        .filter(|((_, _, s), _)| s == &b'A')
-        .map(|(s, _)| (s, Vec::new()))
-        .collect();
-
-    for (p, z_cycles) in position_cycles.iter_mut() {
-        let mut steps = 0;
-        let mut z_checkpoint = 0;
-
-        while z_cycles.len() <= 1 || p != &z_cycles[0].0 {
-            *p = if path[steps % path.len()] == b'L' {
-                &map.get(p).unwrap().left
-            } else {
-                &map.get(p).unwrap().right
-            };
-            steps += 1;
-
-            if p.2 == b'Z' {
-                z_cycles.push((p, steps - z_checkpoint));
-                z_checkpoint = steps;
-            }
-        }
-    }
+        .map(|(ghost, arrivals)| format!("{ghost}: {arrivals}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    // dbg!(position_cycles);
+pub fn part_one(input: &str) -> Option<u32> {
+    let network = parse_network(input).unwrap();
+    let steps = network.first_arrival(|n| n == "AAA", |n| n == "ZZZ").ok()?;
+    Some(steps as u32)
+}
 
-    let mut step_cycles: Vec<u64> = position_cycles
-        .iter()
-        .map(|(_, z_cycles)| z_cycles[0].1 as u64)
-        .collect();
-    let z_lcm = step_cycles.pop().unwrap();
+/// The first step at which every part two ghost stands on a goal node, or why they never do.
+pub fn ghost_arrival(input: &str) -> Result<u64, String> {
+    parse_network(input)
+        .and_then(|network| network.first_arrival(is_part_two_start, is_part_two_goal))
+        .map_err(|err| err.to_string())
+}
 
-    Some(step_cycles.iter().fold(z_lcm, |acc, c| lcm(acc, *c)))
+pub fn part_two(input: &str) -> Option<u64> {
+    ghost_arrival(input).ok()
 }
 
 #[cfg(test)]
@@ -150,4 +247,71 @@ ZZZ = (ZZZ, ZZZ)",
         ));
         assert_eq!(result, Some(6));
     }
+
+    #[test]
+    fn test_ghost_report() {
+        assert_eq!(
+            ghost_report(&advent_of_code::template::read_file_part(
+                "examples", DAY, 2
+            )),
+            "11A: every 2 steps from 2\n22A: every 6 steps from 3, 6"
+        );
+    }
+
+    // Neither ghost reaches its first goal after exactly one period
+    const UNALIGNED: &str = "L
+
+11A = (11B, XXX)
+11B = (11C, XXX)
+11C = (11Z, XXX)
+11Z = (11C, XXX)
+22A = (22Z, XXX)
+22Z = (22B, XXX)
+22B = (22C, XXX)
+22C = (22Z, XXX)
+XXX = (XXX, XXX)";
+
+    #[test]
+    fn test_unaligned_cycles() {
+        assert_eq!(
+            ghost_report(UNALIGNED),
+            "11A: every 2 steps from 3\n22A: every 3 steps from 1"
+        );
+        assert_eq!(part_two(UNALIGNED), Some(7));
+    }
+
+    #[test]
+    fn test_never_together() {
+        // the second ghost only arrives on even steps, the first only on odd ones
+        let input = "L
+
+11A = (11B, XXX)
+11B = (11C, XXX)
+11C = (11Z, XXX)
+11Z = (11C, XXX)
+22A = (22B, XXX)
+22B = (22Z, XXX)
+22Z = (22B, XXX)
+XXX = (XXX, XXX)";
+        assert_eq!(
+            ghost_arrival(input).unwrap_err(),
+            "ghost from 22A arrives at every 2 steps from 2, never at the same time as the \
+             ghosts before it, which arrive at every 2 steps from 3"
+        );
+        assert_eq!(part_two(input), None);
+    }
+
+    #[test]
+    fn test_start_on_goal() {
+        let network = parse_network("L\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)").unwrap();
+        // a ghost that starts on a goal node is there after no step at all
+        assert_eq!(network.first_arrival(|n| n == "AAA", |n| n == "AAA"), Ok(0));
+        assert_eq!(network.first_arrival(|n| n == "AAA", |n| n == "BBB"), Ok(1));
+        assert!(network.first_arrival(|_| true, |n| n == "AAA").is_err());
+
+        assert_eq!(
+            ghost_arrival("L\n\n11A = (11B, 11B)"),
+            Err("unknown node 11B".to_string())
+        );
+    }
 }
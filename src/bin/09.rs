@@ -4,64 +4,99 @@ use nom::{
     multi::separated_list1,
     IResult,
 };
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
 
 advent_of_code::solution!(9);
 
-fn parse_history(input: &str) -> IResult<&str, Vec<Vec<i32>>> {
-    let (input, reading) = separated_list1(tag(" "), complete::i32)(input)?;
-    Ok((input, vec![reading]))
+/// The lowest degree polynomial through a reading, in Newton form: `p(x) = Σ C(x, k) * Δᵏy₀` where
+/// `Δᵏy₀` is the first value of the `k`th difference level and `x` counts steps from the first
+/// value.
+struct Polynomial {
+    len: usize,
+    differences: Vec<BigInt>,
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Vec<Vec<i32>>>> {
+impl Polynomial {
+    /// Takes differences until a level is all zeros. If that never happens the reading is
+    /// interpolated with degree `len - 1`.
+    fn fit(reading: &[i64]) -> Self {
+        let mut level: Vec<BigInt> = reading.iter().map(|&v| v.into()).collect();
+        let mut differences = Vec::new();
+        while !level.iter().all(Zero::is_zero) {
+            differences.push(level[0].clone());
+            level = level.windows(2).map(|w| &w[1] - &w[0]).collect();
+        }
+
+        Self {
+            len: reading.len(),
+            differences,
+        }
+    }
+
+    /// Degree of the polynomial, counting the zero polynomial as a constant.
+    fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    /// Value at `x` steps from the first value; negative `x` extrapolates backwards.
+    fn at(&self, x: i64) -> BigInt {
+        let x = BigInt::from(x);
+        let mut binomial = BigInt::from(1);
+        let mut value = BigInt::zero();
+        for (k, difference) in self.differences.iter().enumerate() {
+            if k > 0 {
+                // C(x, k) = C(x, k - 1) * (x - k + 1) / k, which is always exact
+                binomial = binomial * (&x - (k - 1)) / k;
+            }
+            value += &binomial * difference;
+        }
+        value
+    }
+
+    /// Value `steps` after the last reading, or before the first for negative `steps`.
+    fn extrapolate(&self, steps: i64) -> BigInt {
+        if steps >= 0 {
+            self.at(self.len as i64 - 1 + steps)
+        } else {
+            self.at(steps)
+        }
+    }
+}
+
+fn parse_history(input: &str) -> IResult<&str, Vec<i64>> {
+    separated_list1(tag(" "), complete::i64)(input)
+}
+
+fn parse_input(input: &str) -> IResult<&str, Vec<Vec<i64>>> {
     separated_list1(newline, parse_history)(input)
 }
 
-fn solve(input: &str, part_one: bool) -> i32 {
-    let (_, mut sensor_readings) = parse_input(input).unwrap();
-
-    sensor_readings
-        .iter_mut()
-        .map(|r| {
-            let mut can_extrapolate = false;
-            while !can_extrapolate {
-                let mut steps: Vec<i32> = Vec::new();
-                let level = &r[r.len() - 1];
-                can_extrapolate = true;
-
-                for i in 0..(level.len() - 1) {
-                    let step = level[i + 1] - level[i];
-                    steps.push(step);
-                    if step != 0 {
-                        can_extrapolate = false;
-                    }
-                }
-                r.push(steps);
-            }
+fn polynomials(input: &str) -> Vec<Polynomial> {
+    let (_, readings) = parse_input(input).unwrap();
+    readings.iter().map(|r| Polynomial::fit(r)).collect()
+}
 
-            if part_one {
-                r.iter().map(|s| s.last().unwrap()).sum::<i32>()
-            } else {
-                r.iter()
-                    .enumerate()
-                    .map(|(i, s)| {
-                        if i % 2 == 0 {
-                            *s.first().unwrap()
-                        } else {
-                            s.first().unwrap() * -1
-                        }
-                    })
-                    .sum::<i32>()
-            }
-        })
+/// Sum over all readings of the value `steps` after the last reading, or before the first one if
+/// `steps` is negative.
+pub fn extrapolate(input: &str, steps: i64) -> BigInt {
+    polynomials(input)
+        .iter()
+        .map(|p| p.extrapolate(steps))
         .sum()
 }
 
-pub fn part_one(input: &str) -> Option<i32> {
-    Some(solve(input, true))
+/// Degree of the polynomial detected for every reading.
+pub fn degrees(input: &str) -> Vec<usize> {
+    polynomials(input).iter().map(Polynomial::degree).collect()
 }
 
-pub fn part_two(input: &str) -> Option<i32> {
-    Some(solve(input, false))
+pub fn part_one(input: &str) -> Option<i64> {
+    extrapolate(input, 1).to_i64()
+}
+
+pub fn part_two(input: &str) -> Option<i64> {
+    extrapolate(input, -1).to_i64()
 }
 
 #[cfg(test)]
@@ -79,4 +114,32 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(2));
     }
+
+    #[test]
+    fn test_extrapolate() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(degrees(&input), [1, 2, 3]);
+        assert_eq!(extrapolate(&input, 0), BigInt::from(15 + 21 + 45));
+        // 18, 21, 24 and 28, 36, 45 and 68, 101, 146 follow the readings
+        assert_eq!(extrapolate(&input, 3), BigInt::from(24 + 45 + 146));
+        // -9, 1 and -19 come three steps before the readings
+        assert_eq!(extrapolate(&input, -3), BigInt::from(-9 + 1 - 19));
+
+        // n³ + 1 for n = 0..4, far past the range of an i64
+        let cube = Polynomial::fit(&[1, 2, 9, 28]);
+        assert_eq!(cube.degree(), 3);
+        assert_eq!(cube.at(-2), BigInt::from(-7));
+        assert_eq!(
+            cube.extrapolate(10_i64.pow(10)).to_string(),
+            "1000000000900000000270000000028"
+        );
+    }
+
+    #[test]
+    fn test_degenerate_readings() {
+        assert_eq!(degrees("0 0 0\n5 5 5\n7"), [0, 0, 0]);
+        assert_eq!(extrapolate("0 0 0\n5 5 5\n7", -4), BigInt::from(12));
+        // too short to reach a zero level, so interpolated through every value
+        assert_eq!(degrees("1 4 9"), [2]);
+    }
 }
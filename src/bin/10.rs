@@ -1,13 +1,13 @@
-use std::collections::VecDeque;
+use std::collections::BTreeSet;
 
 advent_of_code::solution!(10);
 
-#[derive(Debug)]
-struct Animal {
-    x: usize,
-    y: usize,
-    steps: u32,
-}
+type Position = (usize, usize);
+
+const NORTH: u8 = 1 << 0;
+const SOUTH: u8 = 1 << 1;
+const EAST: u8 = 1 << 2;
+const WEST: u8 = 1 << 3;
 
 // 0001 : (1 << 0) : north
 // 0010 : (1 << 1) : south
@@ -16,202 +16,174 @@ struct Animal {
 // 0000 : 0        : ground
 fn parse_tile(tile: &u8) -> u8 {
     match tile {
-        b'|' => (1 << 0) | (1 << 1), // north south : 3
-        b'-' => (1 << 2) | (1 << 3), // east west   : 12
-        b'L' => (1 << 0) | (1 << 2), // north east  : 5
-        b'J' => (1 << 0) | (1 << 3), // north west  : 9
-        b'7' => (1 << 1) | (1 << 3), // south west  : 10
-        b'F' => (1 << 1) | (1 << 2), // south east  : 6
-        b'S' => (1 << 4) - 1,        // north south east west
-        _ => 0,                      // ground
+        b'|' => NORTH | SOUTH,
+        b'-' => EAST | WEST,
+        b'L' => NORTH | EAST,
+        b'J' => NORTH | WEST,
+        b'7' => SOUTH | WEST,
+        b'F' => SOUTH | EAST,
+        b'S' => NORTH | SOUTH | EAST | WEST,
+        _ => 0,
     }
 }
 
-fn parse_input(input: &str) -> (Animal, Vec<Vec<u8>>) {
-    let mut start: Option<Animal> = None;
-    let lines = input.lines();
-    let mut map: Vec<Vec<u8>> = Vec::with_capacity(lines.clone().count());
-
-    for (i, l) in lines.enumerate() {
-        let tile_line: Vec<u8> =
-            l.as_bytes()
-                .iter()
-                .enumerate()
-                .fold(Vec::with_capacity(l.len()), |mut acc, (j, t)| {
-                    if start.is_none() && t == &b'S' {
-                        start = Some(Animal {
-                            x: i,
-                            y: j,
-                            steps: 0,
-                        });
-                    }
-
-                    acc.push(parse_tile(t));
-                    acc
-                });
-
-        map.push(tile_line);
+fn opposite(direction: u8) -> u8 {
+    match direction {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        EAST => WEST,
+        WEST => EAST,
+        _ => unreachable!(),
     }
+}
 
-    (start.unwrap(), map)
+// The neighbor in `direction`, if it is on the map
+fn step(map: &[Vec<u8>], (row, col): Position, direction: u8) -> Option<Position> {
+    let next = match direction {
+        NORTH => (row.checked_sub(1)?, col),
+        SOUTH => (row + 1, col),
+        EAST => (row, col + 1),
+        WEST => (row, col.checked_sub(1)?),
+        _ => unreachable!(),
+    };
+    (next.0 < map.len() && next.1 < map[next.0].len()).then_some(next)
 }
 
-// screw it
-fn clean_tiles(map: &mut Vec<Vec<u8>>) {
-    for i in 0..map.len() {
-        for j in 0..map[0].len() {
-            if map[i][j] == 0 {
-                continue;
+fn parse_input(input: &str) -> (Position, Vec<Vec<u8>>) {
+    let mut start = None;
+    let map = input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            if let Some(j) = l.find('S') {
+                start = Some((i, j));
             }
+            l.as_bytes().iter().map(parse_tile).collect()
+        })
+        .collect();
 
-            if i == 0 || ((map[i - 1][j] >> 1) & 1) & ((map[i][j] >> 0) & 1) == 0 {
-                map[i][j] &= !(1 << 0);
-            }
-            if i == map.len() - 1 || ((map[i + 1][j] >> 0) & 1) & ((map[i][j] >> 1) & 1) == 0 {
-                map[i][j] &= !(1 << 1);
-            }
-            if j == map[0].len() - 1 || ((map[i][j + 1] >> 3) & 1) & ((map[i][j] >> 2) & 1) == 0 {
-                map[i][j] &= !(1 << 2);
-            }
-            if j == 0 || ((map[i][j - 1] >> 2) & 1) & ((map[i][j] >> 3) & 1) == 0 {
-                map[i][j] &= !(1 << 3);
-            }
-        }
-    }
+    (start.unwrap(), map)
 }
 
-fn bfs(map: &mut Vec<Vec<u8>>, start: Animal) -> u32 {
-    let mut animals_move: VecDeque<Animal> = VecDeque::from([start]);
-    let mut animals_meet_steps: Vec<u32> = Vec::new();
+// Keeps only the connections that the neighboring tile connects back to
+fn clean_tiles(map: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    (0..map.len())
+        .map(|i| {
+            (0..map[i].len())
+                .map(|j| {
+                    [NORTH, SOUTH, EAST, WEST]
+                        .into_iter()
+                        .filter(|&d| {
+                            map[i][j] & d != 0
+                                && step(map, (i, j), d)
+                                    .is_some_and(|(ni, nj)| map[ni][nj] & opposite(d) != 0)
+                        })
+                        .fold(0, |tile, d| tile | d)
+                })
+                .collect()
+        })
+        .collect()
+}
 
-    while let Some(Animal { x, y, steps }) = animals_move.pop_front() {
-        let current_tile = &mut map[x][y];
-        if (*current_tile >> 4) & 1 == 1 {
-            animals_meet_steps.push(steps);
-            continue;
-        }
-        *current_tile |= 1 << 4;
+/// The main loop through the start tile.
+struct PipeLoop {
+    /// Tiles of the loop in walking order, starting with the start tile
+    path: Vec<Position>,
+    /// The map with only the connections used by the loop, and the start tile resolved
+    map: Vec<Vec<u8>>,
+}
 
-        if (*current_tile >> 0) & 1 == 1 {
-            animals_move.push_back(Animal {
-                x: x - 1,
-                y: y,
-                steps: steps + 1,
-            });
-        }
-        if (*current_tile >> 1) & 1 == 1 {
-            animals_move.push_back(Animal {
-                x: x + 1,
-                y: y,
-                steps: steps + 1,
-            });
-        }
-        if (*current_tile >> 2) & 1 == 1 {
-            animals_move.push_back(Animal {
-                x: x,
-                y: y + 1,
-                steps: steps + 1,
-            });
-        }
-        if (*current_tile >> 3) & 1 == 1 {
-            animals_move.push_back(Animal {
-                x: x,
-                y: y - 1,
-                steps: steps + 1,
-            });
-        }
-    }
+impl PipeLoop {
+    /// Walks the pipes away from the start tile in each direction until one walk returns to it.
+    fn new(input: &str) -> Option<Self> {
+        let (start, map) = parse_input(input);
+        let mut map = clean_tiles(&map);
 
-    animals_meet_steps.iter().max().unwrap() - 1
-}
+        for first in [NORTH, SOUTH, EAST, WEST] {
+            if map[start.0][start.1] & first == 0 {
+                continue;
+            }
 
-fn dfs(map: &mut Vec<Vec<u8>>, start: &(usize, usize), current: (usize, usize)) -> Option<u32> {
-    if (map[current.0][current.1] >> 4) & 1 == 1 {
-        if start == &current {
-            return Some(0);
-        } else {
-            return None;
-        }
-    }
-    map[current.0][current.1] |= 1 << 4;
+            let mut path = vec![start];
+            let mut position = start;
+            let mut direction = first;
+            while let Some(next) = step(&map, position, direction) {
+                if next == start {
+                    map[start.0][start.1] = first | opposite(direction);
+                    let mut loop_map = vec![vec![0; map[0].len()]; map.len()];
+                    for &(i, j) in &path {
+                        loop_map[i][j] = map[i][j];
+                    }
+                    return Some(Self {
+                        path,
+                        map: loop_map,
+                    });
+                }
 
-    let mut steps: Option<u32> = None;
-    if (map[current.0][current.1] >> 0) & 1 == 1 {
-        if let Some(dfs_steps) = dfs(map, start, (current.0 - 1, current.1)) {
-            steps = Some(steps.unwrap_or_default().max(dfs_steps) + 1);
-        }
-    }
-    if (map[current.0][current.1] >> 1) & 1 == 1 {
-        if let Some(dfs_steps) = dfs(map, start, (current.0 + 1, current.1)) {
-            steps = Some(steps.unwrap_or_default().max(dfs_steps) + 1);
+                let exits = map[next.0][next.1] & !opposite(direction);
+                if exits.count_ones() != 1 {
+                    break;
+                }
+                path.push(next);
+                position = next;
+                direction = exits;
+            }
         }
+
+        None
     }
-    if (map[current.0][current.1] >> 2) & 1 == 1 {
-        if let Some(dfs_steps) = dfs(map, start, (current.0, current.1 + 1)) {
-            steps = Some(steps.unwrap_or_default().max(dfs_steps) + 1);
-        }
+
+    /// Enclosed tile count from the shoelace formula and Pick's theorem: a lattice polygon with
+    /// area `A` and `B` boundary points holds `A - B / 2 + 1` interior points.
+    fn enclosed_count(&self) -> usize {
+        let twice_area = self
+            .path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .map(|(&(r0, c0), &(r1, c1))| (r0 * c1) as i64 - (r1 * c0) as i64)
+            .sum::<i64>()
+            .unsigned_abs() as usize;
+
+        (twice_area - self.path.len()) / 2 + 1
     }
-    if (map[current.0][current.1] >> 3) & 1 == 1 {
-        if let Some(dfs_steps) = dfs(map, start, (current.0, current.1 - 1)) {
-            steps = Some(steps.unwrap_or_default().max(dfs_steps) + 1);
+
+    /// Enclosed tiles found by scanning every row and counting how often it crosses the loop.
+    /// Only tiles connecting north toggle the count, so running along a horizontal stretch
+    /// crosses it exactly when its ends turn in opposite directions.
+    fn scan_interior(&self) -> BTreeSet<Position> {
+        let mut interior = BTreeSet::new();
+        for (i, row) in self.map.iter().enumerate() {
+            let mut inside = false;
+            for (j, &tile) in row.iter().enumerate() {
+                if tile & NORTH != 0 {
+                    inside = !inside;
+                } else if tile == 0 && inside {
+                    interior.insert((i, j));
+                }
+            }
         }
+        interior
     }
+}
 
-    steps
+/// The loop tiles in walking order from the start tile, and the tiles the loop encloses.
+pub fn trace(input: &str) -> Option<(Vec<Position>, BTreeSet<Position>)> {
+    let pipe_loop = PipeLoop::new(input)?;
+    let interior = pipe_loop.scan_interior();
+    Some((pipe_loop.path, interior))
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    let (start, mut map) = parse_input(&input);
-    clean_tiles(&mut map);
-    Some(bfs(&mut map, start))
-    // Some(dfs(&mut map, &(animal.x, animal.y), (animal.x, animal.y)).unwrap() / 2)
+    Some(PipeLoop::new(input)?.path.len() as u32 / 2)
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let (animal, mut map) = parse_input(&input);
-    clean_tiles(&mut map);
-    dfs(&mut map, &(animal.x, animal.y), (animal.x, animal.y));
-
-    for r in map.iter_mut() {
-        for c in r.iter_mut() {
-            if (*c >> 4) & 1 == 0 {
-                *c = 0;
-            }
-        }
-    }
-
-    let mut in_tiles = 0;
-    for r in &map {
-        let mut crosses_count: u8 = 0;
-
-        let mut cross_flag = 0;
-        for c in r {
-            cross_flag ^= ((1 << 2) - 1) & c;
-            if cross_flag == (1 << 1) | 1 {
-                crosses_count += 1;
-                cross_flag = 0;
-            }
-        }
-
-        if crosses_count % 2 == 1 {
-            continue;
-        }
-
-        let mut crosses_count: u8 = 0;
-        for c in r {
-            if *c == 0 && crosses_count % 2 == 1 {
-                in_tiles += 1;
-            } else {
-                cross_flag ^= ((1 << 2) - 1) & c;
-                if cross_flag == (1 << 1) | 1 {
-                    crosses_count += 1;
-                    cross_flag = 0;
-                }
-            }
-        }
-    }
+    Some(PipeLoop::new(input)?.enclosed_count() as u32)
+}
 
-    Some(in_tiles)
+/// Part two, counting the enclosed tiles row by row.
+pub fn part_two_scan(input: &str) -> Option<u32> {
+    Some(PipeLoop::new(input)?.scan_interior().len() as u32)
 }
 
 #[cfg(test)]
@@ -253,4 +225,26 @@ mod tests {
         ));
         assert_eq!(result, Some(10));
     }
+
+    #[test]
+    fn test_part_two_scan() {
+        for part in 1..=6 {
+            let input = advent_of_code::template::read_file_part("examples", DAY, part);
+            assert_eq!(part_two_scan(&input), part_two(&input));
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let (path, interior) = trace(&advent_of_code::template::read_file_part(
+            "examples", DAY, 3,
+        ))
+        .unwrap();
+        assert_eq!(path.len(), 46);
+        assert_eq!(path[..3], [(1, 1), (2, 1), (3, 1)]);
+        assert_eq!(
+            interior.into_iter().collect::<Vec<_>>(),
+            [(6, 2), (6, 3), (6, 7), (6, 8)]
+        );
+    }
 }
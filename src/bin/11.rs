@@ -1,130 +1,131 @@
-use std::collections::HashSet;
-
 advent_of_code::solution!(11);
 
-fn parse_input(input: &str) -> Vec<Vec<u8>> {
-    input.lines().map(|l| l.bytes().collect()).collect()
+/// Galaxy positions along with how many empty rows and columns come before every row and column.
+struct Image {
+    galaxies: Vec<(usize, usize)>,
+    empty_rows_before: Vec<usize>,
+    empty_cols_before: Vec<usize>,
 }
 
-fn expand_rows(image: &mut Vec<Vec<u8>>, gap: usize) -> Vec<Vec<u8>> {
-    let mut expanded_image = Vec::with_capacity(image.len() * 2);
-    while let Some(row) = image.pop() {
-        if !row.contains(&b'#') {
-            for _ in 1..gap {
-                expanded_image.push(row.clone());
-            }
-        }
-        expanded_image.push(row);
-    }
-
-    expanded_image
+// Running count of the empty lines before each index
+fn empty_before(occupied: &[bool]) -> Vec<usize> {
+    occupied
+        .iter()
+        .scan(0, |empty, &o| {
+            let before = *empty;
+            *empty += !o as usize;
+            Some(before)
+        })
+        .collect()
 }
 
-fn transpose(image: &mut Vec<Vec<u8>>) -> Vec<Vec<u8>> {
-    let rows = image.len();
-    let cols = image[0].len();
-
-    (0..cols)
-        .map(|c| (0..rows).map(|r| image[r][c]).collect())
-        .collect()
+// Sum of |a - b| over all pairs, from the sorted values and their prefix sums
+fn pairwise_sum(mut values: Vec<u128>) -> u128 {
+    values.sort_unstable();
+    let mut prefix = 0;
+    let mut total = 0;
+    for (i, v) in values.into_iter().enumerate() {
+        total += v * i as u128 - prefix;
+        prefix += v;
+    }
+    total
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let mut image = parse_input(input);
+impl Image {
+    fn new(input: &str) -> Self {
+        let image: Vec<&[u8]> = input.lines().map(|l| l.as_bytes()).collect();
+        let cols = image.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        let mut galaxies = Vec::new();
+        let mut rows_occupied = vec![false; image.len()];
+        let mut cols_occupied = vec![false; cols];
+        for (i, row) in image.iter().enumerate() {
+            for (j, &space) in row.iter().enumerate() {
+                if space == b'#' {
+                    galaxies.push((i, j));
+                    rows_occupied[i] = true;
+                    cols_occupied[j] = true;
+                }
+            }
+        }
+
+        Self {
+            galaxies,
+            empty_rows_before: empty_before(&rows_occupied),
+            empty_cols_before: empty_before(&cols_occupied),
+        }
+    }
 
-    let mut expanded_image: Vec<Vec<u8>> = expand_rows(&mut image, 2);
-    expanded_image = transpose(&mut expanded_image);
-    expanded_image = expand_rows(&mut expanded_image, 2);
+    /// Galaxy positions once every empty row and column is replaced by `factor` of them. A
+    /// factor of 1 leaves the image as is.
+    fn expanded(&self, factor: u128) -> Vec<(u128, u128)> {
+        assert!(factor >= 1, "expansion factor must be at least 1");
+        self.galaxies
+            .iter()
+            .map(|&(i, j)| {
+                (
+                    i as u128 + (factor - 1) * self.empty_rows_before[i] as u128,
+                    j as u128 + (factor - 1) * self.empty_cols_before[j] as u128,
+                )
+            })
+            .collect()
+    }
 
-    let rows = expanded_image[0].len();
-    let galaxies: Vec<(usize, usize)> = expanded_image
-        .iter()
-        .flatten()
-        .enumerate()
-        .filter_map(|(i, space)| {
-            if space == &b'.' {
-                None
-            } else {
-                Some((i / rows, i % rows))
-            }
-        })
-        .collect();
+    /// Sum of the distances between every pair of galaxies. Manhattan distance splits into the
+    /// two axes, and each axis is summed in O(n log n) by sorting.
+    fn distance_sum(&self, factor: u128) -> u128 {
+        let (rows, cols) = self.expanded(factor).into_iter().unzip();
+        pairwise_sum(rows) + pairwise_sum(cols)
+    }
 
-    Some(
+    fn distance_matrix(&self, factor: u128) -> Vec<Vec<u128>> {
+        let galaxies = self.expanded(factor);
         galaxies
             .iter()
-            .enumerate()
-            .map(|(i, s1)| {
+            .map(|a| {
                 galaxies
                     .iter()
-                    .skip(i + 1)
-                    .map(|s2| s1.0.abs_diff(s2.0) + s1.1.abs_diff(s2.1))
-                    .sum::<usize>()
+                    .map(|b| a.0.abs_diff(b.0) + a.1.abs_diff(b.1))
+                    .collect()
             })
-            .sum::<usize>() as u32,
-    )
+            .collect()
+    }
 }
 
-pub fn part_two(input: &str) -> Option<u64> {
-    let image = parse_input(input);
+/// Distances between every pair of galaxies, numbered in reading order from 0.
+pub fn distance_matrix(input: &str, factor: u128) -> Vec<Vec<u128>> {
+    Image::new(input).distance_matrix(factor)
+}
 
-    let col_size = image[0].len();
-    let galaxies: Vec<(usize, usize)> = image
+/// For every galaxy, the closest other galaxy and its distance. Ties go to the galaxy that comes
+/// first in reading order.
+pub fn nearest_neighbors(input: &str, factor: u128) -> Vec<Option<(usize, u128)>> {
+    distance_matrix(input, factor)
         .iter()
-        .flatten()
         .enumerate()
-        .filter_map(|(i, space)| {
-            if space == &b'.' {
-                None
-            } else {
-                Some((i / col_size, i % col_size))
-            }
+        .map(|(i, distances)| {
+            distances
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .min_by_key(|&(_, d)| d)
+                .map(|(j, &d)| (j, d))
         })
-        .collect();
+        .collect()
+}
 
-    let empty_rows: HashSet<usize> = image
-        .iter()
-        .enumerate()
-        .filter_map(|(i, row)| if row.contains(&b'#') { None } else { Some(i) })
-        .fold(HashSet::with_capacity(image.len()), |mut acc, idx| {
-            acc.insert(idx);
-            acc
-        });
-    let empty_cols: HashSet<usize> = (0..col_size)
-        .filter_map(|j| {
-            for i in 0..image.len() {
-                if image[i][j] == b'#' {
-                    return None;
-                }
-            }
-            Some(j)
-        })
-        .fold(HashSet::with_capacity(image.len()), |mut acc, idx| {
-            acc.insert(idx);
-            acc
-        });
+/// Sum of the distances between every pair of galaxies after expanding the empty space `factor`
+/// times.
+pub fn distance_sum(input: &str, factor: u128) -> u128 {
+    Image::new(input).distance_sum(factor)
+}
 
-    let gap: usize = 1000000;
-    Some(
-        galaxies
-            .iter()
-            .enumerate()
-            .map(|(i, s1)| {
-                galaxies
-                    .iter()
-                    .skip(i + 1)
-                    .map(|s2| {
-                        ((s1.0.min(s2.0))..(s1.0.max(s2.0)))
-                            .map(|step| if empty_rows.contains(&step) { gap } else { 1 })
-                            .sum::<usize>()
-                            + ((s1.1.min(s2.1))..(s1.1.max(s2.1)))
-                                .map(|step| if empty_cols.contains(&step) { gap } else { 1 })
-                                .sum::<usize>()
-                    })
-                    .sum::<usize>()
-            })
-            .sum::<usize>() as u64,
-    )
+pub fn part_one(input: &str) -> Option<u64> {
+    distance_sum(input, 2).try_into().ok()
+}
+
+pub fn part_two(input: &str) -> Option<u64> {
+    distance_sum(input, 1_000_000).try_into().ok()
 }
 
 #[cfg(test)]
@@ -142,4 +143,35 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(82000210));
     }
+
+    #[test]
+    fn test_expansion_factors() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(distance_sum(&input, 10), 1030);
+        assert_eq!(distance_sum(&input, 100), 8410);
+        assert_eq!(distance_sum(&input, 1), 292);
+        // every extra copy of the empty space adds the same amount to the sum
+        assert_eq!(
+            distance_sum(&input, 10_u128.pow(30)),
+            distance_sum(&input, 2) + (10_u128.pow(30) - 2) * (distance_sum(&input, 2) - 292)
+        );
+    }
+
+    #[test]
+    fn test_distances() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let matrix = distance_matrix(&input, 2);
+        assert_eq!(matrix[4][8], 9);
+        assert_eq!(matrix[0][6], 15);
+        assert_eq!(matrix[2][5], 17);
+        assert_eq!(matrix[7][8], 5);
+        assert_eq!(
+            matrix.iter().flatten().sum::<u128>(),
+            2 * distance_sum(&input, 2)
+        );
+
+        let nearest = nearest_neighbors(&input, 2);
+        assert_eq!(nearest[7], Some((8, 5)));
+        assert_eq!(nearest_neighbors("#", 2), [None]);
+    }
 }
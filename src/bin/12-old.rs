@@ -1,3 +1,6 @@
+// The original solution, kept as written
+#![allow(clippy::type_complexity, clippy::unnecessary_to_owned)]
+
 use std::collections::HashMap;
 
 use nom::{
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -18,113 +16,265 @@ enum Spring {
     Unknown,
 }
 
-#[derive(Eq, Hash, PartialEq, Clone)]
+impl Spring {
+    fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' => Some(Spring::Operational),
+            '#' => Some(Spring::Damaged),
+            '?' => Some(Spring::Unknown),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Spring::Operational => '.',
+            Spring::Damaged => '#',
+            Spring::Unknown => '?',
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
 struct ConditionRecord {
     springs: Vec<Spring>,
     groupings: Vec<usize>,
 }
 
-fn parse_condition_record(input: &str) -> IResult<&str, ConditionRecord> {
-    let (input, (springs, groupings)) = separated_pair(
-        many1(alt((
-            complete::char('.').map(|_| Spring::Operational),
-            complete::char('#').map(|_| Spring::Damaged),
-            complete::char('?').map(|_| Spring::Unknown),
-        ))),
-        tag(" "),
-        separated_list1(tag(","), complete::u32.map(|num| num as usize)),
-    )(input)?;
-    Ok((input, ConditionRecord { springs, groupings }))
+/// Number of arrangements of `springs[i..]` matching `groupings[g..]` for every (i, g), stored
+/// flat with `i` major.
+struct ArrangementTable {
+    groups: usize,
+    counts: Vec<u64>,
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<ConditionRecord>> {
-    separated_list1(newline, parse_condition_record)(input)
+impl ArrangementTable {
+    fn get(&self, i: usize, g: usize) -> u64 {
+        self.counts[i * (self.groups + 1) + g]
+    }
 }
 
-// ty icub3d from yt for awesome explanation 
-fn permutation(
-    springs: &[Spring],
-    groupings: &[usize],
-    cache: &mut HashMap<ConditionRecord, u64>,
-) -> u64 {
-    let cond_record = ConditionRecord {
-        springs: springs.to_vec(),
-        groupings: groupings.to_vec(),
-    };
-    if let Some(&arrangements) = cache.get(&cond_record) {
-        return arrangements;
+impl ConditionRecord {
+    /// `factor` copies of the record, with the springs joined by `separator`.
+    fn unfold(&self, factor: usize, separator: Spring) -> ConditionRecord {
+        let mut springs = Vec::with_capacity((self.springs.len() + 1) * factor);
+        for i in 0..factor {
+            if i > 0 {
+                springs.push(separator);
+            }
+            springs.extend(&self.springs);
+        }
+
+        ConditionRecord {
+            springs,
+            groupings: self.groupings.repeat(factor),
+        }
     }
 
-    if groupings.is_empty() {
-        let is_valid = if springs.iter().any(|s| *s == Spring::Damaged) {
-            0
-        } else {
-            1
+    // Where the group starting at `i` would need to end, if `len` springs from `i` can all be
+    // damaged and the one after them (if any) can be operational
+    fn group_end(&self, runs: &[usize], i: usize, len: usize) -> Option<usize> {
+        let end = i + len;
+        (runs[i] >= len && self.springs.get(end) != Some(&Spring::Damaged)).then_some(end)
+    }
+
+    // Length of the run of possibly damaged springs starting at each index
+    fn runs(&self) -> Vec<usize> {
+        let mut runs = vec![0; self.springs.len() + 1];
+        for (i, s) in self.springs.iter().enumerate().rev() {
+            if *s != Spring::Operational {
+                runs[i] = runs[i + 1] + 1;
+            }
+        }
+        runs
+    }
+
+    /// Fills the table from the end of the record: the spring at `i` is either operational, or
+    /// starts group `g` followed by an operational spring.
+    fn table(&self) -> ArrangementTable {
+        let (n, m) = (self.springs.len(), self.groupings.len());
+        let runs = self.runs();
+        let mut table = ArrangementTable {
+            groups: m,
+            counts: vec![0; (n + 1) * (m + 1)],
         };
-        cache.insert(cond_record, is_valid);
-        return is_valid;
+        table.counts[n * (m + 1) + m] = 1;
+
+        for i in (0..n).rev() {
+            for g in 0..=m {
+                let mut count = 0;
+                if self.springs[i] != Spring::Damaged {
+                    count += table.get(i + 1, g);
+                }
+                if g < m {
+                    if let Some(end) = self.group_end(&runs, i, self.groupings[g]) {
+                        count += table.get((end + 1).min(n), g + 1);
+                    }
+                }
+                table.counts[i * (m + 1) + g] = count;
+            }
+        }
+        table
     }
 
-    let dmg_count = springs.iter().filter(|s| *s == &Spring::Damaged).count();
-    let unknown_count = springs.iter().filter(|s| *s == &Spring::Unknown).count();
-    let dmg_count_target = groupings.iter().sum();
-    if dmg_count + unknown_count < dmg_count_target || dmg_count > dmg_count_target {
-        cache.insert(cond_record, 0);
-        return 0;
+    fn arrangement_count(&self) -> u64 {
+        self.table().get(0, 0)
     }
 
-    if springs[0] == Spring::Operational {
-        let arrangements = permutation(&springs[1..], groupings, cache);
-        cache.insert(cond_record, arrangements);
-        return arrangements;
+    fn arrangements(self) -> Arrangements {
+        let table = self.table();
+        let stack = if table.get(0, 0) > 0 {
+            vec![(0, 0, Vec::with_capacity(self.springs.len()))]
+        } else {
+            Vec::new()
+        };
+
+        Arrangements {
+            runs: self.runs(),
+            record: self,
+            table,
+            stack,
+        }
     }
+}
 
-    let mut total_arrangements = 0;
-    let group_target = groupings[0];
-    let current_group_size = springs[0..group_target]
-        .iter()
-        .filter(|s| *s != &Spring::Operational)
-        .count();
-    if current_group_size == group_target
-        && ((group_target < springs.len() && springs[group_target] != Spring::Damaged)
-            || springs.len() == group_target)
-    {
-        let next = springs.len().min(group_target + 1);
-        total_arrangements = permutation(&springs[next..], &groupings[1..], cache);
+/// Lazily yields every concrete arrangement of a record. Only branches with at least one
+/// arrangement are explored, so every step makes progress towards the next one.
+struct Arrangements {
+    record: ConditionRecord,
+    runs: Vec<usize>,
+    table: ArrangementTable,
+    // (spring index, group index, springs so far)
+    stack: Vec<(usize, usize, Vec<Spring>)>,
+}
+
+impl Iterator for Arrangements {
+    type Item = Vec<Spring>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.record.springs.len();
+        while let Some((i, g, springs)) = self.stack.pop() {
+            if i == n {
+                return Some(springs);
+            }
+
+            // Pushed first so that arrangements come out with damaged springs as early as possible
+            if self.record.springs[i] != Spring::Damaged && self.table.get(i + 1, g) > 0 {
+                let mut operational = springs.clone();
+                operational.push(Spring::Operational);
+                self.stack.push((i + 1, g, operational));
+            }
+            if g < self.record.groupings.len() {
+                let len = self.record.groupings[g];
+                if let Some(end) = self.record.group_end(&self.runs, i, len) {
+                    let next = (end + 1).min(n);
+                    if self.table.get(next, g + 1) > 0 {
+                        let mut group = springs;
+                        group.extend(std::iter::repeat_n(Spring::Damaged, len));
+                        group.extend(std::iter::repeat_n(Spring::Operational, next - end));
+                        self.stack.push((next, g + 1, group));
+                    }
+                }
+            }
+        }
+        None
     }
 
-    if springs[0] == Spring::Unknown {
-        total_arrangements += permutation(&springs[1..], groupings, cache);
+    // Counting does not need to build the arrangements
+    fn count(self) -> usize {
+        self.stack
+            .iter()
+            .map(|&(i, g, _)| self.table.get(i, g) as usize)
+            .sum()
     }
+}
+
+fn parse_condition_record(input: &str) -> IResult<&str, ConditionRecord> {
+    let (input, (springs, groupings)) = separated_pair(
+        many1(alt((
+            complete::char('.').map(|_| Spring::Operational),
+            complete::char('#').map(|_| Spring::Damaged),
+            complete::char('?').map(|_| Spring::Unknown),
+        ))),
+        tag(" "),
+        separated_list1(tag(","), complete::u32.map(|num| num as usize)),
+    )(input)?;
+    Ok((input, ConditionRecord { springs, groupings }))
+}
+
+fn parse_input(input: &str) -> IResult<&str, Vec<ConditionRecord>> {
+    separated_list1(newline, parse_condition_record)(input)
+}
 
-    cache.insert(cond_record, total_arrangements);
-    total_arrangements
+/// Every arrangement of a single record such as `???.### 1,1,3`, in the same notation.
+pub fn arrangements(record: &str) -> impl Iterator<Item = String> {
+    let (_, record) = parse_condition_record(record).unwrap();
+    record
+        .arrangements()
+        .map(|springs| springs.into_iter().map(Spring::symbol).collect())
 }
 
-fn solve(cond_records: &Vec<ConditionRecord>) -> u64 {
-    let mut cache: HashMap<ConditionRecord, u64> = HashMap::new();
+/// Total arrangements after unfolding every record `factor` times, joining the copies with the
+/// `separator` spring.
+pub fn arrangement_count(input: &str, factor: usize, separator: char) -> u64 {
+    let separator = Spring::from_symbol(separator).expect("separator must be one of .#?");
+    let (_, cond_records) = parse_input(input).unwrap();
     cond_records
         .iter()
-        .map(|ConditionRecord { springs, groupings }| permutation(springs, groupings, &mut cache))
+        .map(|r| r.unfold(factor, separator).arrangement_count())
         .sum()
 }
 
 pub fn part_one(input: &str) -> Option<u64> {
-    let (_, cond_records) = parse_input(input).unwrap();
-    Some(solve(&cond_records))
+    Some(arrangement_count(input, 1, '?'))
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let (_, mut cond_records) = parse_input(input).unwrap();
-    for ConditionRecord { springs, groupings } in cond_records.iter_mut() {
-        let mut unfolded_part = vec![Spring::Unknown];
-        unfolded_part.extend(springs.iter());
-        springs.extend(unfolded_part.iter().cycle().take(4 * unfolded_part.len()));
+    Some(arrangement_count(input, 5, '?'))
+}
 
-        groupings.extend(groupings.clone().iter().cycle().take(4 * groupings.len()));
+// The brute force from 12-old.rs, trying every way to fill in the unknown springs, as an oracle
+// for the tests
+#[cfg(test)]
+mod brute_force {
+    fn fillings(springs: &[u8]) -> Vec<Vec<u8>> {
+        match springs.iter().position(|&c| c == b'?') {
+            None => vec![springs.to_vec()],
+            Some(i) => [b'.', b'#']
+                .iter()
+                .flat_map(|&c| {
+                    let mut filled = springs.to_vec();
+                    filled[i] = c;
+                    fillings(&filled)
+                })
+                .collect(),
+        }
     }
 
-    Some(solve(&cond_records))
+    fn damaged_groups(springs: &[u8]) -> Vec<usize> {
+        springs
+            .split(|&c| c == b'.')
+            .filter(|group| !group.is_empty())
+            .map(<[u8]>::len)
+            .collect()
+    }
+
+    pub fn part_one(input: &str) -> Option<u64> {
+        let mut total = 0;
+        for line in input.lines() {
+            let (springs, groups) = line.split_once(' ')?;
+            let groups = groups
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<usize>, _>>()
+                .ok()?;
+            total += fillings(springs.as_bytes())
+                .iter()
+                .filter(|filled| damaged_groups(filled) == groups)
+                .count() as u64;
+        }
+        Some(total)
+    }
 }
 
 #[cfg(test)]
@@ -142,4 +292,58 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(525152));
     }
+
+    #[test]
+    fn test_arrangements() {
+        assert_eq!(
+            arrangements("?###???????? 3,2,1")
+                .take(3)
+                .collect::<Vec<_>>(),
+            [".###.##.#...", ".###.##..#..", ".###.##...#."]
+        );
+        assert_eq!(arrangements("?###???????? 3,2,1").count(), 10);
+        assert_eq!(arrangements("??? 4").next(), None);
+
+        let mut iter = arrangements("?.?.? 1");
+        iter.next();
+        // counting the rest skips building them
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn test_unfold() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let unfolded: String = input
+            .lines()
+            .map(|l| {
+                let (springs, groups) = l.split_once(' ').unwrap();
+                format!("{springs}.{springs} {groups},{groups}\n")
+            })
+            .collect();
+        assert_eq!(
+            brute_force::part_one(unfolded.trim_end()),
+            Some(arrangement_count(&input, 2, '.'))
+        );
+        assert_eq!(arrangement_count("??? 1", 2, '#'), 4);
+        assert_eq!(arrangement_count("? 1", 3, '?'), 1);
+    }
+
+    #[test]
+    fn test_brute_force_oracle() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let records = [
+            input.as_str(),
+            "??????? 2,1",
+            "#??.?#?##? 1,4",
+            "?#???..??#? 1,1,2",
+            ".??#?.???#?? 3,2",
+        ];
+        for record in records {
+            assert_eq!(brute_force::part_one(record), part_one(record), "{record}");
+        }
+        for record in records.iter().flat_map(|r| r.lines()) {
+            let listed = arrangements(record).collect::<Vec<_>>();
+            assert_eq!(Some(listed.len() as u64), part_one(record), "{record}");
+        }
+    }
 }
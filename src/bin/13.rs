@@ -7,119 +7,176 @@ use nom::{
 
 advent_of_code::solution!(13);
 
-fn parse_reflection(input: &str) -> IResult<&str, Vec<&[u8]>> {
-    let (input, reflection) = separated_list1(newline, take_till1(|c| c == '\n'))(input)?;
-    Ok((
-        input,
-        reflection.iter().map(|line| line.as_bytes()).collect(),
-    ))
+/// A row or column of a pattern as a bitset over as many 64-bit words as it needs.
+type Line = Vec<u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    /// Mirror between two rows
+    Horizontal,
+    /// Mirror between two columns
+    Vertical,
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Vec<&[u8]>>> {
-    separated_list1(tag("\n\n"), parse_reflection)(input)
+/// A mirror placed after the first `position` rows or columns, valid once the spaces at
+/// `smudges` (row, column) are flipped.
+#[derive(Debug, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    position: usize,
+    smudges: Vec<(usize, usize)>,
 }
 
-fn conversion(space: &u8) -> u64 {
-    match space {
-        b'.' => 0,
-        b'#' => 1,
-        _ => unreachable!(),
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Horizontal => self.position * 100,
+            Axis::Vertical => self.position,
+        }
     }
 }
 
-fn to_bitmap(map: &Vec<&[u8]>) -> (Vec<u64>, Vec<u64>) {
-    let row_bitmap: Vec<u64> = map
-        .iter()
-        .map(|row| row.iter().fold(0, |acc, c| (acc << 1) | conversion(c)))
-        .collect();
+struct Pattern {
+    rows: Vec<Line>,
+    cols: Vec<Line>,
+}
 
-    let rows = map.len();
-    let cols = map[0].len();
-    let col_bitmap: Vec<u64> = (0..cols)
-        .map(|j| (0..rows).fold(0, |acc, i| (acc << 1) | conversion(&map[i][j])))
-        .collect();
+fn to_line(spaces: impl Iterator<Item = u8>) -> Line {
+    let mut line = Vec::new();
+    for (i, space) in spaces.enumerate() {
+        if i % 64 == 0 {
+            line.push(0);
+        }
+        if space == b'#' {
+            line[i / 64] |= 1 << (i % 64);
+        }
+    }
+    line
+}
 
-    (row_bitmap, col_bitmap)
+// Positions of the set bits
+fn bits(line: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    line.iter().enumerate().flat_map(|(w, &word)| {
+        (0..64)
+            .filter(move |b| (word >> b) & 1 == 1)
+            .map(move |b| w * 64 + b)
+    })
 }
 
-fn identify_reflection(map: &[u64], smudg: Option<usize>) -> Option<usize> {
-    for i in 1..map.len() {
-        let mut left = i - 1;
-        let mut right = i;
-        while 0 < left && right < map.len() - 1 {
-            if map[left] != map[right] {
-                break;
-            }
-            left -= 1;
-            right += 1;
+impl Pattern {
+    fn new(map: &[&[u8]]) -> Self {
+        let cols = map[0].len();
+        Self {
+            rows: map.iter().map(|row| to_line(row.iter().copied())).collect(),
+            cols: (0..cols)
+                .map(|j| to_line(map.iter().map(|row| row[j])))
+                .collect(),
         }
+    }
 
-        if (left == 0 || right == map.len() - 1) && map[left] == map[right] {
-            if let Some(smudge_idx) = smudg {
-                if left <= smudge_idx && smudge_idx <= right {
-                    return Some(i);
+    /// Every mirror along `axis` that needs exactly `smudges` spaces flipped. Mirrored lines are
+    /// compared with XOR, and the popcount gives the number of differing spaces. Smudges are
+    /// reported on the line before the mirror, though flipping the mirrored space works as well.
+    fn reflections_along(&self, axis: Axis, smudges: u32) -> Vec<Reflection> {
+        let lines = match axis {
+            Axis::Horizontal => &self.rows,
+            Axis::Vertical => &self.cols,
+        };
+
+        (1..lines.len())
+            .filter_map(|position| {
+                let pairs = (0..position).rev().zip(position..lines.len());
+                let mut differences = 0;
+                let mut flipped = Vec::new();
+                for (before, after) in pairs {
+                    let xor: Line = lines[before]
+                        .iter()
+                        .zip(&lines[after])
+                        .map(|(a, b)| a ^ b)
+                        .collect();
+                    differences += xor.iter().map(|w| w.count_ones()).sum::<u32>();
+                    if differences > smudges {
+                        return None;
+                    }
+                    flipped.extend(bits(&xor).map(|other| match axis {
+                        Axis::Horizontal => (before, other),
+                        Axis::Vertical => (other, before),
+                    }));
                 }
-            } else {
-                return Some(i);
-            }
-        }
+
+                (differences == smudges).then(|| {
+                    flipped.sort_unstable();
+                    Reflection {
+                        axis,
+                        position,
+                        smudges: flipped,
+                    }
+                })
+            })
+            .collect()
     }
 
-    None
+    fn reflections(&self, smudges: u32) -> Vec<Reflection> {
+        let mut reflections = self.reflections_along(Axis::Horizontal, smudges);
+        reflections.extend(self.reflections_along(Axis::Vertical, smudges));
+        reflections
+    }
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, reflections_map) = parse_input(input).unwrap();
-    let reflections_bitmap: Vec<(Vec<u64>, Vec<u64>)> =
-        reflections_map.iter().map(|map| to_bitmap(map)).collect();
-
-    Some(
-        reflections_bitmap
-            .iter()
-            .map(|(horizontal, vertical)| {
-                if let Some(reflect_count) = identify_reflection(horizontal, None) {
-                    reflect_count * 100
-                } else {
-                    identify_reflection(vertical, None).unwrap()
-                }
-            })
-            .sum::<usize>() as u32,
-    )
+fn parse_reflection(input: &str) -> IResult<&str, Vec<&[u8]>> {
+    let (input, reflection) = separated_list1(newline, take_till1(|c| c == '\n'))(input)?;
+    Ok((
+        input,
+        reflection.iter().map(|line| line.as_bytes()).collect(),
+    ))
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let (_, reflections_map) = parse_input(input).unwrap();
-    let mut reflections_bitmap: Vec<(Vec<u64>, Vec<u64>)> =
-        reflections_map.iter().map(|map| to_bitmap(map)).collect();
-
-    Some(
-        reflections_bitmap
-            .iter_mut()
-            .map(|(horizontal, vertical)| {
-                for i in 0..horizontal.len() {
-                    for n in (0..vertical.len()).rev() {
-                        horizontal[i] ^= 1 << n;
-                        if let Some(reflect_count) = identify_reflection(&horizontal, Some(i)) {
-                            return reflect_count * 100;
-                        }
-                        horizontal[i] ^= 1 << n;
-                    }
-                }
+fn parse_input(input: &str) -> IResult<&str, Vec<Vec<&[u8]>>> {
+    separated_list1(tag("\n\n"), parse_reflection)(input)
+}
 
-                for i in 0..vertical.len() {
-                    for n in (0..horizontal.len()).rev() {
-                        vertical[i] ^= 1 << n;
-                        if let Some(reflect_count) = identify_reflection(&vertical, Some(i)) {
-                            return reflect_count;
-                        }
-                        vertical[i] ^= 1 << n;
-                    }
-                }
+fn patterns(input: &str) -> Vec<Pattern> {
+    let (_, maps) = parse_input(input).unwrap();
+    maps.iter().map(|map| Pattern::new(map)).collect()
+}
 
-                unreachable!()
+/// Lists every mirror of every pattern that needs exactly `smudges` spaces flipped, with the
+/// (row, column) of those spaces.
+pub fn describe_reflections(input: &str, smudges: u32) -> String {
+    patterns(input)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, pattern)| {
+            pattern.reflections(smudges).into_iter().map(move |r| {
+                let (axis, line) = match r.axis {
+                    Axis::Horizontal => ("horizontal", "row"),
+                    Axis::Vertical => ("vertical", "column"),
+                };
+                format!(
+                    "pattern {i}: {axis} mirror after {line} {}, smudges {:?}",
+                    r.position, r.smudges
+                )
             })
-            .sum::<usize>() as u32,
-    )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Sums up every mirror that needs exactly `smudges` spaces flipped.
+fn summarize(input: &str, smudges: u32) -> usize {
+    patterns(input)
+        .iter()
+        .flat_map(|p| p.reflections(smudges))
+        .map(|r| r.summary())
+        .sum()
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    Some(summarize(input, 0) as u32)
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    Some(summarize(input, 1) as u32)
 }
 
 #[cfg(test)]
@@ -137,4 +194,41 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(400));
     }
+
+    #[test]
+    fn test_describe_reflections() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            describe_reflections(&input, 0),
+            "pattern 0: vertical mirror after column 5, smudges []\n\
+             pattern 1: horizontal mirror after row 4, smudges []"
+        );
+        assert_eq!(
+            describe_reflections(&input, 1),
+            "pattern 0: horizontal mirror after row 3, smudges [(0, 0)]\n\
+             pattern 1: horizontal mirror after row 1, smudges [(0, 4)]"
+        );
+    }
+
+    #[test]
+    fn test_wide_patterns() {
+        // 100 columns mirrored after column 50, with two smudges past the first word
+        let row: String = (0..50)
+            .map(|j| if j % 3 == 0 { '#' } else { '.' })
+            .collect();
+        let mirrored: String = row.chars().rev().collect();
+        let mut pattern: Vec<String> = vec![format!("{row}{mirrored}"); 3];
+        pattern[1].replace_range(70..71, "#");
+        pattern[2].replace_range(99..100, ".");
+        let input = pattern.join("\n");
+
+        let pattern = &patterns(&input)[0];
+        assert!(!pattern.reflections(0).iter().any(|r| r.position == 50));
+        let reflections = pattern.reflections(2);
+        assert!(reflections.contains(&Reflection {
+            axis: Axis::Vertical,
+            position: 50,
+            smudges: vec![(1, 29), (2, 0)],
+        }));
+    }
 }
@@ -1,160 +1,186 @@
-use std::collections::HashMap;
-
-use nom::{
-    bytes::complete::take_till1, character::complete::newline, multi::separated_list1, IResult,
-};
+use advent_of_code::cycle;
 
 advent_of_code::solution!(14);
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
-enum Space {
-    RoundRock,
-    CubeRock,
-    EmptySpace,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Vec<Space>>> {
-    let (input, map) = separated_list1(newline, take_till1(|c| c == '\n'))(input)?;
-    Ok((
-        input,
-        map.iter()
-            .map(|row| {
-                row.as_bytes()
-                    .iter()
-                    .map(|c| match c {
-                        b'O' => Space::RoundRock,
-                        b'#' => Space::CubeRock,
-                        b'.' => Space::EmptySpace,
-                        _ => unreachable!(),
-                    })
-                    .collect()
-            })
-            .collect(),
-    ))
-}
-
-fn tilt_north(map: &mut Vec<Vec<Space>>) {
-    for i in 0..map.len() {
-        for j in 0..map[0].len() {
-            if map[i][j] != Space::RoundRock {
-                continue;
-            }
+impl Direction {
+    fn parse(c: char) -> Direction {
+        match c {
+            'N' => Direction::North,
+            'W' => Direction::West,
+            'S' => Direction::South,
+            'E' => Direction::East,
+            _ => panic!("unknown direction {c}"),
+        }
+    }
 
-            let mut row_idx = i;
-            while row_idx > 0 && map[row_idx - 1][j] == Space::EmptySpace {
-                map[row_idx][j] = Space::EmptySpace;
-                row_idx -= 1;
-                map[row_idx][j] = Space::RoundRock;
-            }
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::West => Direction::East,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
         }
     }
 }
 
-fn tilt_west(map: &mut Vec<Vec<Space>>) {
-    for row in map.iter_mut() {
-        for j in 0..row.len() {
-            if row[j] != Space::RoundRock {
-                continue;
-            }
+/// Round rocks as one bitset per row, bit `j` standing for column `j`.
+type Rocks = Vec<u128>;
 
-            let mut col_idx = j;
-            while col_idx > 0 && row[col_idx - 1] == Space::EmptySpace {
-                row[col_idx] = Space::EmptySpace;
-                col_idx -= 1;
-                row[col_idx] = Space::RoundRock;
-            }
-        }
-    }
+/// The fixed part of the platform: its size and the cube-shaped rocks.
+struct Platform {
+    width: usize,
+    cubes: Vec<u128>,
 }
 
-fn tilt_south(map: &mut Vec<Vec<Space>>) {
-    for i in (0..map.len()).rev() {
-        for j in 0..map[0].len() {
-            if map[i][j] != Space::RoundRock {
-                continue;
-            }
+impl Platform {
+    fn mask(&self) -> u128 {
+        u128::MAX >> (128 - self.width)
+    }
 
-            let mut row_idx = i;
-            while row_idx < map.len() - 1 && map[row_idx + 1][j] == Space::EmptySpace {
-                map[row_idx][j] = Space::EmptySpace;
-                row_idx += 1;
-                map[row_idx][j] = Space::RoundRock;
-            }
+    // Row `i` of `bits` with every set bit moved one space towards `direction`, dropping the ones
+    // that fall off
+    fn shifted(&self, bits: &[u128], direction: Direction, i: usize) -> u128 {
+        match direction {
+            Direction::North => bits.get(i + 1).copied().unwrap_or(0),
+            Direction::South => i.checked_sub(1).map_or(0, |above| bits[above]),
+            Direction::West => bits[i] >> 1,
+            Direction::East => (bits[i] << 1) & self.mask(),
         }
     }
-}
 
-fn tilt_east(map: &mut Vec<Vec<Space>>) {
-    for row in map.iter_mut() {
-        for j in (0..row.len()).rev() {
-            if row[j] != Space::RoundRock {
-                continue;
+    /// Rolls every round rock as far as it goes. Each round moves all rocks that have a free space
+    /// in front of them by one, so it takes at most as many rounds as the platform is long. The
+    /// rocks moving in a round go in `moved`, so the rounds do not allocate.
+    fn tilt(&self, rocks: &mut Rocks, direction: Direction, moved: &mut Vec<u128>) {
+        loop {
+            moved.clear();
+            moved.extend(
+                (0..rocks.len())
+                    .map(|i| self.shifted(rocks, direction, i) & !(rocks[i] | self.cubes[i])),
+            );
+            if moved.iter().all(|&row| row == 0) {
+                break;
             }
 
-            let mut col_idx = j;
-            while col_idx < row.len() - 1 && row[col_idx + 1] == Space::EmptySpace {
-                row[col_idx] = Space::EmptySpace;
-                col_idx += 1;
-                row[col_idx] = Space::RoundRock;
+            for (i, row) in rocks.iter_mut().enumerate() {
+                *row = (*row & !self.shifted(moved, direction.opposite(), i)) | moved[i];
             }
         }
     }
-}
-
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, mut map) = parse_input(input).unwrap();
 
-    tilt_north(&mut map);
+    fn tilt_all(&self, rocks: &Rocks, sequence: &[Direction]) -> Rocks {
+        let mut rocks = rocks.clone();
+        let mut moved = Vec::with_capacity(rocks.len());
+        for &direction in sequence {
+            self.tilt(&mut rocks, direction, &mut moved);
+        }
+        rocks
+    }
 
-    Some(
-        map.iter()
+    /// Total load on the `direction` support beams: each rounded rock counts its distance from
+    /// the opposite edge, plus one.
+    fn load(&self, rocks: &Rocks, direction: Direction) -> u32 {
+        let height = rocks.len() as u32;
+        let width = self.width as u32;
+        rocks
+            .iter()
             .enumerate()
-            .map(|(i, row)| {
-                row.iter()
-                    .filter(|space| space == &&Space::RoundRock)
-                    .count()
-                    * (map.len() - i)
+            .map(|(i, &row)| {
+                let i = i as u32;
+                match direction {
+                    Direction::North => row.count_ones() * (height - i),
+                    Direction::South => row.count_ones() * (i + 1),
+                    Direction::West => (0..width)
+                        .filter(|j| (row >> j) & 1 == 1)
+                        .map(|j| width - j)
+                        .sum(),
+                    Direction::East => (0..width)
+                        .filter(|j| (row >> j) & 1 == 1)
+                        .map(|j| j + 1)
+                        .sum(),
+                }
+            })
+            .sum()
+    }
+
+    /// The rocks after running the tilt `sequence` `repeats` times. Rock positions repeat after a
+    /// while, so only the tilts up to the end of the first cycle are run.
+    fn after_repeats(&self, rocks: &Rocks, sequence: &[Direction], repeats: usize) -> Rocks {
+        let step = |rocks: &Rocks| self.tilt_all(rocks, sequence);
+        let rock_cycle = cycle::hashed(rocks, step);
+        cycle::nth(rocks, step, repeats, &rock_cycle)
+    }
+
+    fn render(&self, rocks: &Rocks) -> String {
+        rocks
+            .iter()
+            .zip(&self.cubes)
+            .map(|(round, cube)| {
+                (0..self.width)
+                    .map(|j| match ((round >> j) & 1, (cube >> j) & 1) {
+                        (1, _) => 'O',
+                        (_, 1) => '#',
+                        _ => '.',
+                    })
+                    .collect::<String>()
             })
-            .sum::<usize>() as u32,
-    )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let (_, mut map) = parse_input(input).unwrap();
+fn parse_input(input: &str) -> (Platform, Rocks) {
+    let width = input.lines().map(str::len).max().unwrap_or(0);
+    assert!(
+        (1..=128).contains(&width),
+        "platforms are 1 to 128 columns wide"
+    );
 
-    let mut seen: HashMap<Vec<Vec<Space>>, u32> = HashMap::new();
+    let row_bits = |line: &str, rock: u8| {
+        line.bytes()
+            .enumerate()
+            .filter(|&(_, c)| c == rock)
+            .fold(0_u128, |row, (j, _)| row | 1 << j)
+    };
+    let rocks = input.lines().map(|l| row_bits(l, b'O')).collect();
+    let cubes = input.lines().map(|l| row_bits(l, b'#')).collect();
 
-    let cycle_limit = 1000000000;
-    let mut i = 0;
+    (Platform { width, cubes }, rocks)
+}
 
-    while i < cycle_limit {
-        tilt_north(&mut map);
-        tilt_west(&mut map);
-        tilt_south(&mut map);
-        tilt_east(&mut map);
+fn parse_sequence(sequence: &str) -> Vec<Direction> {
+    sequence.chars().map(Direction::parse).collect()
+}
 
-        i += 1;
+/// The platform after running the tilt `sequence` (e.g. `NWSE`) `repeats` times.
+pub fn tilted(input: &str, sequence: &str, repeats: usize) -> String {
+    let (platform, rocks) = parse_input(input);
+    let rocks = platform.after_repeats(&rocks, &parse_sequence(sequence), repeats);
+    platform.render(&rocks)
+}
 
-        if let Some(seen_len) = seen.get(&map) {
-            let cycle_len = i - seen_len;
-            let remaining = cycle_limit - i;
-            i += (remaining / cycle_len) * cycle_len;
-        } else {
-            seen.insert(map.clone(), i);
-        }
-    }
+/// Load on the `support` beams (`N`, `W`, `S` or `E`) after running the tilt `sequence` `repeats`
+/// times.
+pub fn load_after(input: &str, sequence: &str, repeats: usize, support: char) -> u32 {
+    let (platform, rocks) = parse_input(input);
+    let rocks = platform.after_repeats(&rocks, &parse_sequence(sequence), repeats);
+    platform.load(&rocks, Direction::parse(support))
+}
 
-    Some(
-        map.iter()
-            .enumerate()
-            .map(|(i, row)| {
-                row.iter()
-                    .filter(|space| space == &&Space::RoundRock)
-                    .count()
-                    * (map.len() - i)
-            })
-            .sum::<usize>() as u32,
-    )
+pub fn part_one(input: &str) -> Option<u32> {
+    Some(load_after(input, "N", 1, 'N'))
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    Some(load_after(input, "NWSE", 1000000000, 'N'))
 }
 
 #[cfg(test)]
@@ -172,4 +198,47 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(64));
     }
+
+    #[test]
+    fn test_load_after_spins() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        // loads after 1..=10 spin cycles follow a period of 7 from the 3rd cycle on
+        let loads: Vec<u32> = (1..=10)
+            .map(|n| load_after(&input, "NWSE", n, 'N'))
+            .collect();
+        assert_eq!(loads[2..], [69, 69, 65, 64, 65, 63, 68, 69]);
+        assert_eq!(load_after(&input, "NWSE", 0, 'N'), 104);
+    }
+
+    #[test]
+    fn test_tilt_sequences() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            tilted(&input, "NWSE", 1),
+            ".....#....\n\
+             ....#...O#\n\
+             ...OO##...\n\
+             .OO#......\n\
+             .....OOO#.\n\
+             .O#...O#.#\n\
+             ....O#....\n\
+             ......OOOO\n\
+             #...O###..\n\
+             #..OO#...."
+        );
+        // tilting twice in a row changes nothing
+        assert_eq!(tilted(&input, "NNEW", 3), tilted(&input, "NEW", 3));
+
+        // rocks count their distance from the edge opposite the support beams
+        assert_eq!(load_after("O.O#O", "W", 1, 'W'), 5 + 4 + 1);
+        assert_eq!(load_after("O.O#O", "E", 1, 'E'), 2 + 3 + 5);
+        assert_eq!(load_after("O\n.\nO", "S", 1, 'N'), 1 + 2);
+        assert_eq!(load_after("O\n.\nO", "S", 1, 'S'), 2 + 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "platforms are 1 to 128 columns wide")]
+    fn test_empty_platform() {
+        tilted("\n\n", "N", 1);
+    }
 }
@@ -1,10 +1,12 @@
+use std::collections::HashMap;
+
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_till1},
     character::complete::{self, alpha1},
     multi::separated_list1,
     sequence::{separated_pair, terminated},
-    IResult,
+    IResult, Parser,
 };
 
 advent_of_code::solution!(15);
@@ -13,97 +15,230 @@ fn parse_input_p1(input: &str) -> IResult<&str, Vec<&str>> {
     separated_list1(tag(","), take_till1(|c| c == ',' || c == '\n'))(input)
 }
 
-fn hash(code: &str, part_one: bool) -> u32 {
-    let mut hash_num = 0;
-    for c in code.bytes() {
-        if part_one || (c != b'=' && c != b'-') {
-            hash_num = (hash_num + c as u32) * 17 % 256
-        } else {
-            break;
-        }
-    }
-    hash_num
-
-    // code.bytes()
-    //     .fold(0, |acc, c| {
-    //         (acc + c as u32) * 17 % 256
-    //     })
+fn hash(code: &str) -> u32 {
+    code.bytes().fold(0, |acc, c| (acc + c as u32) * 17 % 256)
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
     let (_, init_seq) = parse_input_p1(input).unwrap();
-    Some(init_seq.iter().map(|code| hash(code, true)).sum())
+    Some(init_seq.iter().map(|code| hash(code)).sum())
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
-struct Code<'a> {
-    code: &'a str,
-    focal_len: Option<u32>,
+struct Lens<'a> {
+    label: &'a str,
+    focal_len: u32,
 }
 
-fn parse_lens_dash(input: &str) -> IResult<&str, Code> {
-    let (input, code) = terminated(alpha1, tag("-"))(input)?;
-    Ok((
-        input,
-        Code {
-            code,
-            focal_len: None,
-        },
-    ))
+/// A box of lenses in insertion order. Removed lenses leave an empty slot behind so that the
+/// slots of the others stay valid; the slots are compacted once most of them are empty.
+#[derive(Debug, Default, Clone)]
+struct LensBox<'a> {
+    slots: Vec<Option<Lens<'a>>>,
+    index: HashMap<&'a str, usize>,
 }
 
-fn parse_lens_equals(input: &str) -> IResult<&str, Code> {
-    let (input, (code, focal_len)) = separated_pair(alpha1, tag("="), complete::u32)(input)?;
-    Ok((
-        input,
-        Code {
-            code,
-            focal_len: Some(focal_len),
-        },
-    ))
+impl<'a> LensBox<'a> {
+    fn insert(&mut self, lens: Lens<'a>) {
+        match self.index.get(lens.label) {
+            Some(&slot) => self.slots[slot] = Some(lens),
+            None => {
+                self.index.insert(lens.label, self.slots.len());
+                self.slots.push(Some(lens));
+            }
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> Option<Lens<'a>> {
+        let lens = self.slots[self.index.remove(label)?].take();
+        if self.slots.len() > 2 * self.index.len() {
+            self.slots.retain(Option::is_some);
+            for (slot, lens) in self.slots.iter().enumerate() {
+                self.index.insert(lens.unwrap().label, slot);
+            }
+        }
+        lens
+    }
+
+    fn lenses(&self) -> impl Iterator<Item = &Lens<'a>> {
+        self.slots.iter().flatten()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
 }
 
-fn parse_input_p2(input: &str) -> IResult<&str, Vec<Code>> {
-    separated_list1(
-        alt((tag(","), tag("\n"))),
-        alt((parse_lens_equals, parse_lens_dash)),
-    )(input)
+/// The 256 boxes of the HASHMAP procedure.
+#[derive(Debug, Clone)]
+struct LensBoxes<'a> {
+    boxes: Vec<LensBox<'a>>,
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let (_, init_seq) = parse_input_p2(input).unwrap();
-
-    let mut boxes: Vec<Vec<Code>> = vec![vec![]; 256];
-    for code in &init_seq {
-        let hash = hash(code.code, false) as usize;
-        let box_ref = &mut boxes[hash];
-
-        let box_code_idx = box_ref.iter().position(|c| c.code == code.code);
-        if let Some(code_idx) = box_code_idx {
-            if code.focal_len.is_some() {
-                box_ref[code_idx] = *code;
-            } else {
-                box_ref.remove(code_idx);
+impl<'a> LensBoxes<'a> {
+    fn new() -> Self {
+        Self {
+            boxes: vec![LensBox::default(); 256],
+        }
+    }
+
+    /// Puts the lens in the box its label hashes to, replacing the lens with the same label.
+    fn insert(&mut self, lens: Lens<'a>) {
+        self.boxes[hash(lens.label) as usize].insert(lens);
+    }
+
+    fn remove(&mut self, label: &str) -> Option<Lens<'a>> {
+        self.boxes[hash(label) as usize].remove(label)
+    }
+
+    fn apply(&mut self, operation: &Operation<'a>) {
+        match *operation {
+            Operation::Insert(lens) => self.insert(lens),
+            Operation::Remove(label) => {
+                self.remove(label);
             }
-        } else if code.focal_len.is_some() {
-            box_ref.push(*code);
         }
     }
 
-    Some(
-        boxes
+    fn focusing_power(&self) -> u32 {
+        self.boxes
             .iter()
             .enumerate()
             .map(|(i, b)| {
-                b.iter()
+                b.lenses()
                     .enumerate()
-                    .map(|(j, code)| (1 + i as u32) * (1 + j as u32) * code.focal_len.unwrap())
+                    .map(|(j, lens)| (1 + i as u32) * (1 + j as u32) * lens.focal_len)
                     .sum::<u32>()
             })
-            .sum(),
+            .sum()
+    }
+
+    /// The non-empty boxes, in the format of the puzzle's walkthrough.
+    fn render(&self) -> String {
+        self.boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !b.is_empty())
+            .map(|(i, b)| {
+                let lenses: Vec<String> = b
+                    .lenses()
+                    .map(|l| format!("[{} {}]", l.label, l.focal_len))
+                    .collect();
+                format!("Box {i}: {}", lenses.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Operation<'a> {
+    Insert(Lens<'a>),
+    Remove(&'a str),
+}
+
+/// An initialization sequence that can be stepped through, undone and replayed.
+struct Initialization<'a> {
+    steps: Vec<(&'a str, Operation<'a>)>,
+    applied: usize,
+    boxes: LensBoxes<'a>,
+}
+
+impl<'a> Initialization<'a> {
+    fn new(steps: Vec<(&'a str, Operation<'a>)>) -> Self {
+        Self {
+            steps,
+            applied: 0,
+            boxes: LensBoxes::new(),
+        }
+    }
+
+    /// Applies the next step and returns its text, if any steps are left.
+    fn step(&mut self) -> Option<&'a str> {
+        let (text, operation) = self.steps.get(self.applied)?;
+        self.boxes.apply(operation);
+        self.applied += 1;
+        Some(text)
+    }
+
+    fn run(&mut self) {
+        while self.step().is_some() {}
+    }
+
+    /// Rewinds to the state after the first `applied` steps by replaying them from empty boxes.
+    /// Removing a lens forgets where it was, so replaying is the only way back.
+    fn replay(&mut self, applied: usize) {
+        self.boxes = LensBoxes::new();
+        self.applied = 0;
+        while self.applied < applied && self.step().is_some() {}
+    }
+
+    /// Takes back the last `steps` applied steps.
+    fn undo(&mut self, steps: usize) {
+        self.replay(self.applied.saturating_sub(steps));
+    }
+
+    /// Runs the remaining steps, showing the boxes after each one like the puzzle's walkthrough.
+    fn trace(&mut self) -> String {
+        let mut trace = Vec::new();
+        while let Some(text) = self.step() {
+            trace.push(format!("After \"{text}\":\n{}", self.boxes.render()));
+        }
+        trace.join("\n\n")
+    }
+}
+
+fn parse_lens_dash(input: &str) -> IResult<&str, Operation<'_>> {
+    terminated(alpha1, tag("-"))
+        .map(Operation::Remove)
+        .parse(input)
+}
+
+fn parse_lens_equals(input: &str) -> IResult<&str, Operation<'_>> {
+    separated_pair(alpha1, tag("="), complete::u32)
+        .map(|(label, focal_len)| Operation::Insert(Lens { label, focal_len }))
+        .parse(input)
+}
+
+fn parse_input_p2(input: &str) -> Initialization<'_> {
+    let (_, steps) = parse_input_p1(input).unwrap();
+    Initialization::new(
+        steps
+            .into_iter()
+            .map(|text| {
+                let (_, operation) = alt((parse_lens_equals, parse_lens_dash))(text).unwrap();
+                (text, operation)
+            })
+            .collect(),
     )
 }
 
+/// The boxes after every step of the initialization sequence.
+pub fn trace(input: &str) -> String {
+    parse_input_p2(input).trace()
+}
+
+/// Focusing power after only the first `steps` steps of the initialization sequence.
+pub fn focusing_power_after(input: &str, steps: usize) -> u32 {
+    let mut init = parse_input_p2(input);
+    init.replay(steps);
+    init.boxes.focusing_power()
+}
+
+/// The boxes after the whole initialization sequence except its last `steps` steps.
+pub fn boxes_without_last(input: &str, steps: usize) -> String {
+    let mut init = parse_input_p2(input);
+    init.run();
+    init.undo(steps);
+    init.boxes.render()
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    let mut init = parse_input_p2(input);
+    init.run();
+    Some(init.boxes.focusing_power())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,4 +254,71 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(145));
     }
+
+    #[test]
+    fn test_trace() {
+        let trace = trace(&advent_of_code::template::read_file("examples", DAY));
+        assert!(trace.starts_with(
+            "After \"rn=1\":\n\
+             Box 0: [rn 1]\n\
+             \n\
+             After \"cm-\":\n\
+             Box 0: [rn 1]\n\
+             \n\
+             After \"qp=3\":\n\
+             Box 0: [rn 1]\n\
+             Box 1: [qp 3]\n"
+        ));
+        assert!(trace.ends_with(
+            "After \"ot=7\":\n\
+             Box 0: [rn 1] [cm 2]\n\
+             Box 3: [ot 7] [ab 5] [pc 6]"
+        ));
+    }
+
+    #[test]
+    fn test_undo() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let mut init = parse_input_p2(&input);
+        init.run();
+        // taking back `pc=6` and `ot=7`
+        init.undo(2);
+        assert_eq!(
+            init.boxes.render(),
+            "Box 0: [rn 1] [cm 2]\nBox 3: [ot 9] [ab 5]"
+        );
+        assert_eq!(init.boxes.focusing_power(), focusing_power_after(&input, 9));
+        init.run();
+        assert_eq!(init.boxes.focusing_power(), 145);
+        assert_eq!(focusing_power_after(&input, 0), 0);
+        assert_eq!(boxes_without_last(&input, 100), "");
+    }
+
+    #[test]
+    fn test_lens_box_order() {
+        let mut lens_box = LensBox::default();
+        let labels = ["a", "b", "c", "d", "e"];
+        for (focal_len, label) in labels.into_iter().enumerate() {
+            lens_box.insert(Lens {
+                label,
+                focal_len: focal_len as u32,
+            });
+        }
+        // enough removals to compact the slots
+        for label in ["b", "d", "a"] {
+            assert_eq!(lens_box.remove(label).map(|l| l.label), Some(label));
+        }
+        assert_eq!(lens_box.remove("a"), None);
+        lens_box.insert(Lens {
+            label: "c",
+            focal_len: 9,
+        });
+        lens_box.insert(Lens {
+            label: "b",
+            focal_len: 7,
+        });
+
+        let lenses: Vec<(&str, u32)> = lens_box.lenses().map(|l| (l.label, l.focal_len)).collect();
+        assert_eq!(lenses, [("c", 9), ("e", 4), ("b", 7)]);
+    }
 }
@@ -1,283 +1,317 @@
-use std::collections::VecDeque;
+use std::{collections::HashMap, thread};
 
-use nom::{
-    bytes::complete::take_till1, character::complete::newline, multi::separated_list1, IResult,
+use rustworkx_core::petgraph::{
+    algo::tarjan_scc,
+    graph::{DiGraph, NodeIndex},
 };
 
 advent_of_code::solution!(16);
 
-#[derive(Clone, Copy)]
-enum Space {
-    EmptySpace,
-    MirrorForwardSlash,
-    MirrorBackwardSlash,
-    SplitterPipe,
-    SplitterDash,
-}
-
-#[derive(Clone, Copy)]
-struct Tile {
-    space: Space,
-    directions: u8,
-}
+const NORTH: u8 = 0;
+const SOUTH: u8 = 1;
+const EAST: u8 = 2;
+const WEST: u8 = 3;
 
-#[derive(Clone, Copy)]
+/// A beam entering tile (`row`, `col`) travelling in `direction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct LightBeam {
     direction: u8,
-    x: usize,
-    y: usize,
+    row: usize,
+    col: usize,
 }
 
-fn parse_tile(input: &str) -> IResult<&str, Vec<Tile>> {
-    let (input, row) = take_till1(|c| c == '\n')(input)?;
-    Ok((
-        input,
-        row.as_bytes()
-            .iter()
-            .map(|c| Tile {
-                space: match c {
-                    b'.' => Space::EmptySpace,
-                    b'/' => Space::MirrorForwardSlash,
-                    b'\\' => Space::MirrorBackwardSlash,
-                    b'|' => Space::SplitterPipe,
-                    b'-' => Space::SplitterDash,
-                    _ => unreachable!(),
-                },
-                directions: 0,
-            })
-            .collect(),
-    ))
-}
+/// Set of energized tiles, one bit per tile in row-major order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tiles(Vec<u64>);
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Vec<Tile>>> {
-    separated_list1(newline, parse_tile)(input)
-}
+impl Tiles {
+    fn new(len: usize) -> Self {
+        Tiles(vec![0; len.div_ceil(64)])
+    }
 
-fn move_north(x: usize, y: usize) -> Option<LightBeam> {
-    if x == 0 {
-        None
-    } else {
-        Some(LightBeam {
-            direction: 0,
-            x: x - 1,
-            y,
-        })
+    fn insert(&mut self, tile: usize) {
+        self.0[tile / 64] |= 1 << (tile % 64);
     }
-}
 
-fn move_south(x: usize, y: usize, rows: usize) -> Option<LightBeam> {
-    if x == rows - 1 {
-        None
-    } else {
-        Some(LightBeam {
-            direction: 1,
-            x: x + 1,
-            y,
-        })
+    fn union(&mut self, other: &Tiles) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a |= b;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
     }
 }
 
-fn move_east(x: usize, y: usize, cols: usize) -> Option<LightBeam> {
-    if y == cols - 1 {
-        None
-    } else {
-        Some(LightBeam {
-            direction: 2,
-            x,
-            y: y + 1,
-        })
+// Directions a beam leaves `tile` in after entering it travelling in `direction`
+fn deflect(tile: u8, direction: u8) -> &'static [u8] {
+    match (tile, direction) {
+        (b'/', NORTH) | (b'\\', SOUTH) => &[EAST],
+        (b'/', SOUTH) | (b'\\', NORTH) => &[WEST],
+        (b'/', EAST) | (b'\\', WEST) => &[NORTH],
+        (b'/', WEST) | (b'\\', EAST) => &[SOUTH],
+        (b'|', EAST | WEST) => &[NORTH, SOUTH],
+        (b'-', NORTH | SOUTH) => &[EAST, WEST],
+        (_, NORTH) => &[NORTH],
+        (_, SOUTH) => &[SOUTH],
+        (_, EAST) => &[EAST],
+        _ => &[WEST],
     }
 }
 
-fn move_west(x: usize, y: usize) -> Option<LightBeam> {
-    if y == 0 {
-        None
-    } else {
-        Some(LightBeam {
-            direction: 3,
-            x,
-            y: y - 1,
+/// A stretch of beam from a start or a splitter up to the next splitter it gets split by, or
+/// until it leaves the contraption.
+struct Segment {
+    tiles: Tiles,
+    /// Index of the splitter the beam runs into
+    target: Option<usize>,
+}
+
+/// The contraption as a graph between splitters, where every splitter sends its two beams along a
+/// segment each.
+struct Contraption<'a> {
+    grid: Vec<&'a [u8]>,
+    cols: usize,
+    splitters: HashMap<(usize, usize), usize>,
+    // Tiles energized once a beam reaches each splitter, shared by all splitters of a strongly
+    // connected component
+    energized: Vec<Tiles>,
+}
+
+impl<'a> Contraption<'a> {
+    fn new(input: &'a str) -> Self {
+        let grid: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
+        let cols = grid[0].len();
+        let splitters: HashMap<(usize, usize), usize> = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, &t)| t == b'|' || t == b'-')
+                    .map(move |(j, _)| (i, j))
+            })
+            .enumerate()
+            .map(|(id, pos)| (pos, id))
+            .collect();
+
+        let mut contraption = Self {
+            grid,
+            cols,
+            splitters,
+            energized: Vec::new(),
+        };
+        contraption.energized = contraption.condense();
+        contraption
+    }
+
+    fn step(&self, row: usize, col: usize, direction: u8) -> Option<LightBeam> {
+        let (row, col) = match direction {
+            NORTH => (row.checked_sub(1)?, col),
+            SOUTH => (row + 1, col),
+            EAST => (row, col + 1),
+            _ => (row, col.checked_sub(1)?),
+        };
+        (row < self.grid.len() && col < self.cols).then_some(LightBeam {
+            direction,
+            row,
+            col,
         })
     }
-}
 
-fn solve(mut map: Vec<Vec<Tile>>, start: LightBeam) -> u32 {
-    let mut bfs_queue = VecDeque::from([start]);
+    /// Follows a beam through mirrors and along splitters until it gets split or leaves. A beam
+    /// sent out by `origin` that comes back to it stops there, as the splitter's own beams are
+    /// already accounted for.
+    fn walk(&self, beam: Option<LightBeam>, origin: Option<usize>) -> Segment {
+        let mut tiles = Tiles::new(self.grid.len() * self.cols);
+        let mut beam = beam;
+        while let Some(LightBeam {
+            direction,
+            row,
+            col,
+        }) = beam
+        {
+            tiles.insert(row * self.cols + col);
+            let splitter = self.splitters.get(&(row, col)).copied();
+            let out = deflect(self.grid[row][col], direction);
+            if out.len() == 2 || (splitter.is_some() && splitter == origin) {
+                return Segment {
+                    tiles,
+                    target: splitter,
+                };
+            }
+            beam = self.step(row, col, out[0]);
+        }
 
-    while let Some(LightBeam { direction, x, y }) = bfs_queue.pop_front() {
-        if (map[x][y].directions >> direction) & 1 == 1 {
-            continue;
+        Segment {
+            tiles,
+            target: None,
         }
+    }
 
-        map[x][y].directions |= 1 << direction;
+    /// Builds the splitter graph and unions the energized tiles over its condensation. Tarjan's
+    /// algorithm yields components after all the components they lead to, so those are complete
+    /// by the time they are needed.
+    fn condense(&self) -> Vec<Tiles> {
+        let mut positions = vec![(0, 0); self.splitters.len()];
+        for (&pos, &id) in &self.splitters {
+            positions[id] = pos;
+        }
 
-        match map[x][y].space {
-            Space::EmptySpace => match direction {
-                0 => {
-                    if let Some(light_beam) = move_north(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                1 => {
-                    if let Some(light_beam) = move_south(x, y, map.len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                2 => {
-                    if let Some(light_beam) = move_east(x, y, map[0].len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                3 => {
-                    if let Some(light_beam) = move_west(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                _ => unreachable!(),
-            },
-            Space::MirrorForwardSlash => match direction {
-                0 => {
-                    if let Some(light_beam) = move_east(x, y, map[0].len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                1 => {
-                    if let Some(light_beam) = move_west(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                2 => {
-                    if let Some(light_beam) = move_north(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                3 => {
-                    if let Some(light_beam) = move_south(x, y, map.len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                _ => unreachable!(),
-            },
-            Space::MirrorBackwardSlash => match direction {
-                0 => {
-                    if let Some(light_beam) = move_west(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                1 => {
-                    if let Some(light_beam) = move_east(x, y, map[0].len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                2 => {
-                    if let Some(light_beam) = move_south(x, y, map.len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                3 => {
-                    if let Some(light_beam) = move_north(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                _ => unreachable!(),
-            },
-            Space::SplitterPipe => match direction {
-                0 => {
-                    if let Some(light_beam) = move_north(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                1 => {
-                    if let Some(light_beam) = move_south(x, y, map.len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                2 | 3 => {
-                    if let Some(light_beam) = move_north(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                    if let Some(light_beam) = move_south(x, y, map.len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                _ => unreachable!(),
-            },
-            Space::SplitterDash => match direction {
-                0 | 1 => {
-                    if let Some(light_beam) = move_east(x, y, map[0].len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                    if let Some(light_beam) = move_west(x, y) {
-                        bfs_queue.push_back(light_beam);
-                    }
-                }
-                2 => {
-                    if let Some(light_beam) = move_east(x, y, map[0].len()) {
-                        bfs_queue.push_back(light_beam);
-                    }
+        let mut graph: DiGraph<Tiles, ()> = DiGraph::new();
+        for _ in 0..positions.len() {
+            graph.add_node(Tiles::new(self.grid.len() * self.cols));
+        }
+        for (id, &(row, col)) in positions.iter().enumerate() {
+            let tile = self.grid[row][col];
+            let split = if tile == b'|' {
+                [NORTH, SOUTH]
+            } else {
+                [EAST, WEST]
+            };
+            for direction in split {
+                let segment = self.walk(self.step(row, col, direction), Some(id));
+                graph[NodeIndex::new(id)].union(&segment.tiles);
+                if let Some(target) = segment.target {
+                    graph.add_edge(NodeIndex::new(id), NodeIndex::new(target), ());
                 }
-                3 => {
-                    if let Some(light_beam) = move_west(x, y) {
-                        bfs_queue.push_back(light_beam);
+            }
+            graph[NodeIndex::new(id)].insert(row * self.cols + col);
+        }
+
+        let mut energized: Vec<Option<Tiles>> = vec![None; positions.len()];
+        for component in tarjan_scc(&graph) {
+            let mut tiles = Tiles::new(self.grid.len() * self.cols);
+            for &node in &component {
+                tiles.union(&graph[node]);
+                for next in graph.neighbors(node) {
+                    if let Some(next_tiles) = &energized[next.index()] {
+                        tiles.union(next_tiles);
                     }
                 }
-                _ => unreachable!(),
-            },
+            }
+            for &node in &component {
+                energized[node.index()] = Some(tiles.clone());
+            }
+        }
+
+        energized.into_iter().map(Option::unwrap).collect()
+    }
+
+    fn energize(&self, start: LightBeam) -> usize {
+        let mut segment = self.walk(Some(start), None);
+        if let Some(target) = segment.target {
+            segment.tiles.union(&self.energized[target]);
+        }
+        segment.tiles.len()
+    }
+
+    /// Beams entering from every edge tile, heading inwards.
+    fn starts(&self) -> Vec<LightBeam> {
+        let (rows, cols) = (self.grid.len(), self.cols);
+        let beam = |direction, row, col| LightBeam {
+            direction,
+            row,
+            col,
+        };
+        (0..rows)
+            .flat_map(|i| [beam(EAST, i, 0), beam(WEST, i, cols - 1)])
+            .chain((0..cols).flat_map(|j| [beam(SOUTH, 0, j), beam(NORTH, rows - 1, j)]))
+            .collect()
+    }
+
+    /// Energized tile counts for every start, spread over the available threads.
+    fn energize_all(&self) -> Vec<(LightBeam, usize)> {
+        let starts = self.starts();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = starts.len().div_ceil(threads).max(1);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = starts
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|&start| (start, self.energize(start)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+// Reference implementation following every beam tile by tile
+fn simulate(input: &str, start: LightBeam) -> usize {
+    let contraption = Contraption {
+        grid: input.lines().map(str::as_bytes).collect(),
+        cols: input.lines().next().map_or(0, str::len),
+        splitters: HashMap::new(),
+        energized: Vec::new(),
+    };
+    let mut seen = vec![[false; 4]; contraption.grid.len() * contraption.cols];
+    let mut beams = vec![start];
+    while let Some(LightBeam {
+        direction,
+        row,
+        col,
+    }) = beams.pop()
+    {
+        let visited = &mut seen[row * contraption.cols + col][direction as usize];
+        if *visited {
+            continue;
+        }
+        *visited = true;
+        for &out in deflect(contraption.grid[row][col], direction) {
+            beams.extend(contraption.step(row, col, out));
         }
     }
+    seen.iter().filter(|tile| tile.contains(&true)).count()
+}
 
-    map.iter()
-        .map(|row| row.iter().filter(|space| space.directions != 0).count() as u32)
-        .sum()
+/// Energized tile count for every beam entering from an edge, as the row and column of the first
+/// tile, the direction (`N`, `S`, `E` or `W`) and the count.
+pub fn energized_by_start(input: &str) -> Vec<(usize, usize, char, usize)> {
+    Contraption::new(input)
+        .energize_all()
+        .into_iter()
+        .map(|(start, count)| {
+            let direction = ['N', 'S', 'E', 'W'][start.direction as usize];
+            (start.row, start.col, direction, count)
+        })
+        .collect()
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    let (_, map) = parse_input(input).unwrap();
-
     let start = LightBeam {
-        direction: 2,
-        x: 0,
-        y: 0,
+        direction: EAST,
+        row: 0,
+        col: 0,
     };
-    Some(solve(map, start))
+    Some(Contraption::new(input).energize(start) as u32)
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let (_, map) = parse_input(input).unwrap();
-
-    let mut start_locations: Vec<LightBeam> = Vec::with_capacity(map.len() * 2 + map[0].len() * 2);
-    for i in 0..map.len() {
-        start_locations.push(LightBeam {
-            direction: 2,
-            x: i,
-            y: 0,
-        });
-        start_locations.push(LightBeam {
-            direction: 3,
-            x: i,
-            y: map[0].len() - 1,
-        });
-    }
-    for j in 0..map[0].len() {
-        start_locations.push(LightBeam {
-            direction: 1,
-            x: 0,
-            y: j,
-        });
-        start_locations.push(LightBeam {
-            direction: 0,
-            x: map.len() - 1,
-            y: j,
-        });
-    }
+    Contraption::new(input)
+        .energize_all()
+        .iter()
+        .map(|&(_, count)| count as u32)
+        .max()
+}
 
-    Some(
-        start_locations
-            .iter()
-            .map(|start| solve(map.to_vec(), *start))
-            .max()
-            .unwrap(),
-    )
+/// Part two, simulating the beams from every start separately.
+pub fn part_two_brute_force(input: &str) -> Option<u32> {
+    Contraption::new(input)
+        .starts()
+        .into_iter()
+        .map(|start| simulate(input, start) as u32)
+        .max()
 }
 
 #[cfg(test)]
@@ -295,4 +329,32 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(51));
     }
+
+    #[test]
+    fn test_energized_by_start() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let counts = energized_by_start(&input);
+        assert_eq!(counts.len(), 40);
+        assert!(counts.contains(&(0, 0, 'E', 46)));
+        assert!(counts.contains(&(0, 3, 'S', 51)));
+        assert_eq!(part_two_brute_force(&input), Some(51));
+
+        let contraption = Contraption::new(&input);
+        for start in contraption.starts() {
+            assert_eq!(contraption.energize(start), simulate(&input, start));
+        }
+    }
+
+    #[test]
+    fn test_splitter_cycles() {
+        // the mirrors in the corners make a loop, along which the two splitters feed each other
+        let input = "/..|..\\\n.......\n\\..-../";
+        let energized = Contraption::new(input).energized;
+        assert_eq!(energized.len(), 2);
+        assert_eq!(energized[0], energized[1]);
+        let contraption = Contraption::new(input);
+        for start in contraption.starts() {
+            assert_eq!(contraption.energize(start), simulate(input, start));
+        }
+    }
 }
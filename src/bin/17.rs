@@ -1,240 +1,215 @@
-use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, HashSet},
-};
-
 use nom::{
     bytes::complete::take_till1, character::complete::newline, multi::separated_list1, IResult,
 };
 
 advent_of_code::solution!(17);
 
-fn parse_row(input: &str) -> IResult<&str, Vec<u32>> {
-    let (input, row) = take_till1(|c| c == '\n')(input)?;
-    Ok((input, row.bytes().map(|c| (c - b'0') as u32).collect()))
+// north, south, east, west as (row, column) offsets
+const DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, 1), (0, -1)];
+const ARROWS: [char; 4] = ['^', 'v', '>', '<'];
+
+fn is_reverse(a: usize, b: usize) -> bool {
+    a != b && a / 2 == b / 2
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Vec<u32>>> {
-    let (input, row) = separated_list1(newline, parse_row)(input)?;
-    Ok((input, row))
+/// How far a crucible must and may move in a straight line before turning or stopping.
+#[derive(Debug, Clone, Copy)]
+struct Crucible {
+    min_straight: usize,
+    max_straight: usize,
 }
 
-fn step(
-    current_pos: (usize, usize),
-    direction: (i32, i32),
-    current_heat_loss: u32,
-    map: &[Vec<u32>],
-    current_path: u64,
-    prev_shift: u8,
-    unvisited: &mut BinaryHeap<Reverse<(u32, (usize, usize), u64)>>,
-) {
-    let dst = (
-        (current_pos.0 as i32 + direction.0) as usize,
-        (current_pos.1 as i32 + direction.1) as usize,
-    );
-    let new_heat_loss = current_heat_loss + map[dst.0][dst.1];
-    let dst_path = (current_path >> 4) | (1 << prev_shift);
-    unvisited.push(Reverse((new_heat_loss, dst, dst_path)));
+const CRUCIBLE: Crucible = Crucible {
+    min_straight: 1,
+    max_straight: 3,
+};
+
+const ULTRA_CRUCIBLE: Crucible = Crucible {
+    min_straight: 4,
+    max_straight: 10,
+};
+
+/// A least heat loss route, listing every block entered along with the direction it was entered
+/// in.
+#[derive(Debug, PartialEq, Eq)]
+struct Route {
+    heat_loss: u32,
+    path: Vec<((usize, usize), usize)>,
 }
 
-// 0001 : north
-// 0010 : south
-// 0100 : east
-// 1000 : west
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, map) = parse_input(input).unwrap();
-    let target = (map.len() - 1, map[0].len() - 1);
-
-    let mut visited: HashSet<((usize, usize), u64)> = HashSet::new();
-    let mut unvisited: BinaryHeap<Reverse<(u32, (usize, usize), u64)>> =
-        BinaryHeap::from([Reverse((0, (0, 0), 0))]);
-
-    let prev_shift = 8;
-
-    let (north_lim, south_lim, east_lim, west_lim) = (0..3).fold((0, 0, 0, 0), |acc, _| {
-        (
-            (acc.0 << 4) | 1,
-            (acc.1 << 4) | (1 << 1),
-            (acc.2 << 4) | (1 << 2),
-            (acc.3 << 4) | (1 << 3),
-        )
-    });
-
-    while let Some(Reverse((current_heat_loss, current_pos, current_path))) = unvisited.pop() {
-        if current_pos == target {
-            return Some(current_heat_loss);
-        } else if visited.contains(&(current_pos, current_path)) {
-            continue;
-        }
-        visited.insert((current_pos, current_path));
-
-        if current_path != north_lim
-            && current_pos.0 > 0
-            && (current_path >> (prev_shift + 1)) & 1 == 0
-        {
-            step(
-                current_pos,
-                (-1, 0),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift,
-                &mut unvisited,
-            );
-        }
-        if current_path != south_lim
-            && current_pos.0 < map.len() - 1
-            && (current_path >> prev_shift) & 1 == 0
-        {
-            step(
-                current_pos,
-                (1, 0),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 1,
-                &mut unvisited,
-            );
-        }
-        if current_path != east_lim
-            && current_pos.1 < map[0].len() - 1
-            && (current_path >> (prev_shift + 3)) & 1 == 0
-        {
-            step(
-                current_pos,
-                (0, 1),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 2,
-                &mut unvisited,
-            );
-        }
-        if current_path != west_lim
-            && current_pos.1 > 0
-            && (current_path >> (prev_shift + 2)) & 1 == 0
-        {
-            step(
-                current_pos,
-                (0, -1),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 3,
-                &mut unvisited,
-            );
-        }
-    }
+/// A block, the direction the crucible entered it in and how many blocks it has moved straight,
+/// where a run of 0 stands for the start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    pos: (usize, usize),
+    direction: usize,
+    run: usize,
+}
 
-    None
+struct City {
+    map: Vec<Vec<u32>>,
+    rows: usize,
+    cols: usize,
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let (_, map) = parse_input(input).unwrap();
-    let target = (map.len() - 1, map[0].len() - 1);
-
-    let mut visited: HashSet<((usize, usize), u64)> = HashSet::new();
-    let mut unvisited: BinaryHeap<Reverse<(u32, (usize, usize), u64)>> =
-        BinaryHeap::from([Reverse((0, (0, 0), 0))]);
-
-    let prev_shift = 36;
-    let prev_4_mask = ((1 << 16) - 1) << 24;
-
-    let (north_lim, south_lim, east_lim, west_lim) = (0..10).fold((0, 0, 0, 0), |acc, _| {
-        (
-            (acc.0 << 4) | 1,
-            (acc.1 << 4) | (1 << 1),
-            (acc.2 << 4) | (1 << 2),
-            (acc.3 << 4) | (1 << 3),
-        )
-    });
-
-    while let Some(Reverse((current_heat_loss, current_pos, current_path))) = unvisited.pop() {
-        let current_prev_4 = current_path & prev_4_mask;
-        if current_pos == target
-            && ((current_prev_4 == north_lim & prev_4_mask)
-                || (current_prev_4 == south_lim & prev_4_mask)
-                || (current_prev_4 == east_lim & prev_4_mask)
-                || (current_prev_4 == west_lim & prev_4_mask))
-        {
-            return Some(current_heat_loss);
-        } else if visited.contains(&(current_pos, current_path)) {
-            continue;
-        }
-        visited.insert((current_pos, current_path));
-
-        let mut valid_path = (1 << 4) - 1;
-        if current_path == north_lim
-            || current_path == south_lim
-            || current_path == east_lim
-            || current_path == west_lim
-        {
-            valid_path ^= current_path >> prev_shift;
-        } else if (current_prev_4 != north_lim & prev_4_mask)
-            && (current_prev_4 != south_lim & prev_4_mask)
-            && (current_prev_4 != east_lim & prev_4_mask)
-            && (current_prev_4 != west_lim & prev_4_mask)
-            && current_path != 0
-        {
-            valid_path = current_path >> prev_shift;
-        }
+impl City {
+    fn index(&self, crucible: &Crucible, state: &State) -> usize {
+        ((state.pos.0 * self.cols + state.pos.1) * 4 + state.direction)
+            * (crucible.max_straight + 1)
+            + state.run
+    }
 
-        if valid_path & 1 == 1 && current_pos.0 > 0 && (current_path >> (prev_shift + 1)) & 1 == 0 {
-            step(
-                current_pos,
-                (-1, 0),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift,
-                &mut unvisited,
-            );
-        }
-        if (valid_path >> 1) & 1 == 1
-            && current_pos.0 < map.len() - 1
-            && (current_path >> prev_shift) & 1 == 0
-        {
-            step(
-                current_pos,
-                (1, 0),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 1,
-                &mut unvisited,
-            );
-        }
-        if (valid_path >> 2) & 1 == 1
-            && current_pos.1 < map[0].len() - 1
-            && (current_path >> (prev_shift + 3)) & 1 == 0
-        {
-            step(
-                current_pos,
-                (0, 1),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 2,
-                &mut unvisited,
-            );
+    // Manhattan distance to the factory; every block costs at least 1
+    fn heuristic(&self, (row, col): (usize, usize)) -> u32 {
+        (self.rows - 1 - row + self.cols - 1 - col) as u32
+    }
+
+    fn moves(&self, crucible: &Crucible, state: &State) -> impl Iterator<Item = State> + '_ {
+        let State {
+            pos,
+            direction,
+            run,
+        } = *state;
+        let (min, max) = (crucible.min_straight, crucible.max_straight);
+
+        (0..4).filter_map(move |d| {
+            let straight = d == direction && run > 0;
+            if (straight && run >= max)
+                || (!straight && run > 0 && run < min)
+                || (run > 0 && is_reverse(d, direction))
+            {
+                return None;
+            }
+
+            let row = pos.0.checked_add_signed(DIRECTIONS[d].0)?;
+            let col = pos.1.checked_add_signed(DIRECTIONS[d].1)?;
+            (row < self.rows && col < self.cols).then_some(State {
+                pos: (row, col),
+                direction: d,
+                run: if straight { run + 1 } else { 1 },
+            })
+        })
+    }
+
+    /// A* from the top-left to the bottom-right block. The heuristic is consistent and a move
+    /// raises `heat loss + heuristic` by at most 10, so a ring of 11 buckets serves as the
+    /// priority queue.
+    fn route(&self, crucible: &Crucible) -> Option<Route> {
+        let states = self.rows * self.cols * 4 * (crucible.max_straight + 1);
+        let mut heat_loss = vec![u32::MAX; states];
+        let mut parent = vec![usize::MAX; states];
+        let mut done = vec![false; states];
+        let mut by_index: Vec<Option<State>> = vec![None; states];
+        let mut buckets: Vec<Vec<State>> = vec![Vec::new(); 11];
+        let mut queued = 1;
+
+        let start = State {
+            pos: (0, 0),
+            direction: 0,
+            run: 0,
+        };
+        let start_index = self.index(crucible, &start);
+        heat_loss[start_index] = 0;
+        by_index[start_index] = Some(start);
+        let mut f = self.heuristic(start.pos);
+        buckets[f as usize % 11].push(start);
+
+        while queued > 0 {
+            let Some(state) = buckets[f as usize % 11].pop() else {
+                f += 1;
+                continue;
+            };
+            queued -= 1;
+
+            let index = self.index(crucible, &state);
+            if done[index] || heat_loss[index] + self.heuristic(state.pos) != f {
+                continue;
+            }
+            done[index] = true;
+
+            if state.pos == (self.rows - 1, self.cols - 1) && state.run >= crucible.min_straight {
+                let mut path = Vec::new();
+                let mut current = index;
+                while current != start_index {
+                    let s = by_index[current].unwrap();
+                    path.push((s.pos, s.direction));
+                    current = parent[current];
+                }
+                path.reverse();
+                return Some(Route {
+                    heat_loss: heat_loss[index],
+                    path,
+                });
+            }
+
+            for next in self.moves(crucible, &state) {
+                let next_index = self.index(crucible, &next);
+                let cost = heat_loss[index] + self.map[next.pos.0][next.pos.1];
+                if cost < heat_loss[next_index] {
+                    heat_loss[next_index] = cost;
+                    parent[next_index] = index;
+                    by_index[next_index] = Some(next);
+                    buckets[(cost + self.heuristic(next.pos)) as usize % 11].push(next);
+                    queued += 1;
+                }
+            }
         }
-        if (valid_path >> 3) & 1 == 1
-            && current_pos.1 > 0
-            && (current_path >> (prev_shift + 2)) & 1 == 0
-        {
-            step(
-                current_pos,
-                (0, -1),
-                current_heat_loss,
-                &map,
-                current_path,
-                prev_shift + 3,
-                &mut unvisited,
-            );
+
+        None
+    }
+
+    /// The map with the route drawn in, like the puzzle's examples.
+    fn render(&self, route: &Route) -> String {
+        let mut grid: Vec<Vec<char>> = self
+            .map
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&h| char::from_digit(h, 10).unwrap())
+                    .collect()
+            })
+            .collect();
+        for &((row, col), direction) in &route.path {
+            grid[row][col] = ARROWS[direction];
         }
+        grid.iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
     }
+}
 
-    None
+fn parse_row(input: &str) -> IResult<&str, Vec<u32>> {
+    let (input, row) = take_till1(|c| c == '\n')(input)?;
+    Ok((input, row.bytes().map(|c| (c - b'0') as u32).collect()))
+}
+
+fn parse_input(input: &str) -> City {
+    let (_, map) = separated_list1(newline, parse_row)(input).unwrap();
+    City {
+        rows: map.len(),
+        cols: map[0].len(),
+        map,
+    }
+}
+
+/// The least heat loss route for a crucible that moves between `min_straight` and `max_straight`
+/// blocks before turning, drawn on the map, along with its heat loss.
+pub fn best_route(input: &str, min_straight: usize, max_straight: usize) -> Option<(u32, String)> {
+    let city = parse_input(input);
+    let route = city.route(&Crucible {
+        min_straight,
+        max_straight,
+    })?;
+    Some((route.heat_loss, city.render(&route)))
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    Some(parse_input(input).route(&CRUCIBLE)?.heat_loss)
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    Some(parse_input(input).route(&ULTRA_CRUCIBLE)?.heat_loss)
 }
 
 #[cfg(test)]
@@ -266,4 +241,32 @@ mod tests {
         ));
         assert_eq!(result, Some(71));
     }
+
+    #[test]
+    fn test_best_route() {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 3);
+        assert_eq!(
+            best_route(&input, 4, 10),
+            Some((
+                71,
+                "1>>>>>>>1111\n\
+                 9999999v9991\n\
+                 9999999v9991\n\
+                 9999999v9991\n\
+                 9999999v>>>>"
+                    .to_string()
+            ))
+        );
+
+        // the path of a route adds up to its heat loss
+        let input = advent_of_code::template::read_file_part("examples", DAY, 1);
+        let city = parse_input(&input);
+        let route = city.route(&CRUCIBLE).unwrap();
+        let total: u32 = route.path.iter().map(|&((r, c), _)| city.map[r][c]).sum();
+        assert_eq!(total, 102);
+
+        // too short to ever stop
+        assert_eq!(best_route("12", 2, 3), None);
+        assert_eq!(best_route("12", 1, 1).map(|(h, _)| h), Some(2));
+    }
 }
@@ -1,184 +1,200 @@
-use std::collections::HashMap;
-
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{self, alphanumeric1, newline},
+    bytes::complete::{tag, take_while_m_n},
+    character::complete::{self, newline, one_of},
+    combinator::{map_res, opt},
     multi::separated_list1,
-    sequence::{delimited, preceded},
+    sequence::{delimited, pair, preceded, separated_pair},
     IResult, Parser,
 };
 
 advent_of_code::solution!(18);
 
-struct Dig<'a> {
-    direction: (i32, i32),
+// right, down, left, up as (row, column) offsets, in the order of the hex color's last digit
+const DIRECTIONS: [(i64, i64); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+/// Largest outline drawn, in cells of its bounding box.
+const MAX_RENDER_CELLS: i128 = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    direction: usize,
     count: i64,
-    color: &'a str,
 }
 
-fn parse_dig(input: &str) -> IResult<&str, Dig> {
-    let (input, direction) = alt((
-        complete::char('U').map(|_| (0, 1)),
-        complete::char('D').map(|_| (0, -1)),
-        complete::char('L').map(|_| (-1, 0)),
-        complete::char('R').map(|_| (1, 0)),
-    ))(input)?;
-    let (input, count) = preceded(tag(" "), complete::i64)(input)?;
-    let (input, color) = delimited(tag(" (#"), alphanumeric1, tag(")"))(input)?;
-    Ok((
-        input,
-        Dig {
-            direction,
-            count,
-            color,
-        },
-    ))
+/// Which of the two instructions on a line of the dig plan to follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The `R 6` direction and count.
+    Plain,
+    /// The `(#70c710)` color, holding the count in hex followed by the direction.
+    Color,
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Dig>> {
-    separated_list1(newline, parse_dig)(input)
+/// A line of the dig plan, which may give either or both forms of the instruction.
+struct Dig {
+    plain: Option<Instruction>,
+    color: Option<Instruction>,
 }
 
-fn solve(dig_plan: &[Dig<'_>]) -> u64 {
-    let mut map: HashMap<(i32, i32), u8> = HashMap::new();
-    let mut prev = (0, 0);
-    map.insert(prev, 0);
-
-    for Dig {
-        direction,
-        count,
-        color: _,
-    } in dig_plan
-    {
-        let (prev_dir, current_dir): (u8, u8) = match direction {
-            (0, 1) => (1, 1 << 1),
-            (0, -1) => (1 << 1, 1),
-            (-1, 0) => (1 << 3, 1 << 2),
-            (1, 0) => (1 << 2, 1 << 3),
-            _ => unreachable!(),
-        };
-
-        for _ in 0..*count {
-            *map.get_mut(&prev).unwrap() |= prev_dir;
-            let coord = (prev.0 + direction.0, prev.1 + direction.1);
-            map.insert(coord, current_dir);
-            prev = coord;
+impl Dig {
+    fn instruction(&self, format: Format) -> Option<Instruction> {
+        match format {
+            Format::Plain => self.plain,
+            Format::Color => self.color,
         }
     }
+}
 
-    if let Some(up) = map.get_mut(&(0, 1)) {
-        *up |= 1;
-    }
-    if let Some(down) = map.get_mut(&(0, -1)) {
-        *down |= 1 << 1;
+/// The trench as a closed polygon through the centers of the dug cubes.
+struct Lagoon {
+    vertices: Vec<(i64, i64)>,
+    perimeter: i64,
+}
+
+impl Lagoon {
+    /// Follows the instructions from the origin, or `None` if they do not lead back to it.
+    fn dig(instructions: impl IntoIterator<Item = Instruction>) -> Option<Lagoon> {
+        let mut vertices = vec![(0, 0)];
+        let mut perimeter = 0;
+        for Instruction { direction, count } in instructions {
+            let (row, col) = vertices[vertices.len() - 1];
+            let (dr, dc) = DIRECTIONS[direction];
+            vertices.push((row + dr * count, col + dc * count));
+            perimeter += count;
+        }
+
+        (vertices.pop() == Some((0, 0))).then_some(Lagoon {
+            vertices,
+            perimeter,
+        })
     }
-    if let Some(left) = map.get_mut(&(-1, 0)) {
-        *left |= 1 << 3;
+
+    /// Twice the area enclosed by the polygon, by the shoelace formula.
+    fn double_area(&self) -> i128 {
+        let n = self.vertices.len();
+        (0..n)
+            .map(|i| {
+                let (r1, c1) = self.vertices[i];
+                let (r2, c2) = self.vertices[(i + 1) % n];
+                r1 as i128 * c2 as i128 - r2 as i128 * c1 as i128
+            })
+            .sum::<i128>()
+            .abs()
     }
-    if let Some(right) = map.get_mut(&(1, 0)) {
-        *right |= 1 << 2;
+
+    /// Cubes dug out, counting the trench itself. By Pick's theorem the polygon holds
+    /// `A - b/2 + 1` interior points, and the `b` boundary points are the trench.
+    fn volume(&self) -> i128 {
+        (self.double_area() + self.perimeter as i128) / 2 + 1
     }
 
-    let min_x = map.keys().min_by_key(|(x, _)| x).unwrap().0;
-    let max_x = map.keys().max_by_key(|(x, _)| x).unwrap().0;
-    let min_y = map.keys().min_by_key(|(_, y)| y).unwrap().1;
-    let max_y = map.keys().max_by_key(|(_, y)| y).unwrap().1;
-
-    (min_y..=max_y)
-        .map(|y| {
-            let mut cross_flag: u8 = 0;
-            let total_cross: u32 = (min_x..=max_x)
-                .map(|x| {
-                    if let Some(direction) = map.get(&(x, y)) {
-                        cross_flag ^= ((1 << 2) - 1) & *direction;
-                        if cross_flag == (1 << 1) | 1 {
-                            cross_flag = 0;
-                            return 1;
-                        }
-                    }
-                    0
-                })
-                .sum();
-
-            if total_cross % 2 == 1 {
-                return 0;
+    /// The trench drawn with `#`, like the first picture in the puzzle, or `None` if it covers
+    /// more than [`MAX_RENDER_CELLS`].
+    fn render(&self) -> Option<String> {
+        let min_row = self.vertices.iter().map(|v| v.0).min().unwrap();
+        let max_row = self.vertices.iter().map(|v| v.0).max().unwrap();
+        let min_col = self.vertices.iter().map(|v| v.1).min().unwrap();
+        let max_col = self.vertices.iter().map(|v| v.1).max().unwrap();
+        let cells = (max_row - min_row + 1) as i128 * (max_col - min_col + 1) as i128;
+        if cells > MAX_RENDER_CELLS {
+            return None;
+        }
+        let cols = (max_col - min_col + 1) as usize;
+        let mut grid = vec![vec!['.'; cols]; (max_row - min_row + 1) as usize];
+
+        let n = self.vertices.len();
+        for i in 0..n {
+            let (r1, c1) = self.vertices[i];
+            let (r2, c2) = self.vertices[(i + 1) % n];
+            for row in r1.min(r2)..=r1.max(r2) {
+                for col in c1.min(c2)..=c1.max(c2) {
+                    grid[(row - min_row) as usize][(col - min_col) as usize] = '#';
+                }
             }
+        }
 
-            let mut cross_count = 0;
-            cross_flag = 0;
-            (min_x..=max_x)
-                .map(|x| {
-                    if let Some(direction) = map.get(&(x, y)) {
-                        cross_flag ^= ((1 << 2) - 1) & *direction;
-                        if cross_flag == (1 << 1) | 1 {
-                            cross_count += 1;
-                            cross_flag = 0;
-                        }
-                        0
-                    } else if cross_count % 2 == 1 {
-                        1
-                    } else {
-                        0
-                    }
-                })
-                .sum()
-        })
-        .sum::<u64>()
-        + map.len() as u64
+        Some(
+            grid.iter()
+                .map(|row| row.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, dig_plan) = parse_input(input).unwrap();
-    Some(solve(&dig_plan) as u32)
+fn parse_plain(input: &str) -> IResult<&str, Instruction> {
+    separated_pair(
+        one_of("RDLU").map(|c| "RDLU".find(c).unwrap()),
+        tag(" "),
+        complete::i64,
+    )
+    .map(|(direction, count)| Instruction { direction, count })
+    .parse(input)
 }
 
-pub fn part_two(input: &str) -> Option<i64> {
-    let (_, dig_plan) = parse_input(input).unwrap();
-
-    let mut coordinates: Vec<(i64, i64)> = Vec::new();
-    coordinates.push((0, 0));
-    for (i, Dig { color, .. }) in dig_plan.iter().enumerate() {
-        let count = i64::from_str_radix(&color[..(color.len() - 1)], 16).unwrap();
-        let direction = color.as_bytes().iter().last().unwrap();
-        let prev = coordinates[i];
-        let coord = match direction {
-            b'0' => (prev.0 + count, prev.1),
-            b'1' => (prev.0, prev.1 - count),
-            b'2' => (prev.0 - count, prev.1),
-            b'3' => (prev.0, prev.1 + count),
-            _ => unreachable!(),
-        };
-
-        coordinates.push(coord);
-    }
+fn parse_color(input: &str) -> IResult<&str, Instruction> {
+    delimited(
+        tag("(#"),
+        pair(
+            map_res(
+                take_while_m_n(5, 5, |c: char| c.is_ascii_hexdigit()),
+                |hex| i64::from_str_radix(hex, 16),
+            ),
+            one_of("0123").map(|c| c as usize - '0' as usize),
+        ),
+        tag(")"),
+    )
+    .map(|(count, direction)| Instruction { direction, count })
+    .parse(input)
+}
 
-    // Some(solve(&dig_plan))
+fn parse_dig(input: &str) -> IResult<&str, Dig> {
+    alt((
+        parse_plain
+            .and(opt(preceded(tag(" "), parse_color)))
+            .map(|(plain, color)| Dig {
+                plain: Some(plain),
+                color,
+            }),
+        parse_color.map(|color| Dig {
+            plain: None,
+            color: Some(color),
+        }),
+    ))(input)
+}
 
-    let shoelace = (0..coordinates.len() - 1)
-        .map(|i| {
-            let a = coordinates[i];
-            let b = coordinates[i + 1];
+fn parse_input(input: &str) -> IResult<&str, Vec<Dig>> {
+    separated_list1(newline, parse_dig)(input)
+}
 
-            (a.0 * b.1) - (a.1 * b.0)
-        })
-        .sum::<i64>()
-        .abs()
-        / 2;
+fn lagoon(input: &str, format: Format) -> Option<Lagoon> {
+    let (_, dig_plan) = parse_input(input).unwrap();
+    let instructions = dig_plan
+        .iter()
+        .map(|dig| dig.instruction(format))
+        .collect::<Option<Vec<_>>>()?;
+    Lagoon::dig(instructions)
+}
 
-    let perimeter = (0..coordinates.len() - 1)
-        .map(|i| {
-            let a = coordinates[i];
-            let b = coordinates[i + 1];
+/// Corners of the trench, starting from the origin, or `None` if some line lacks the requested
+/// form or the trench is not closed.
+pub fn vertices(input: &str, format: Format) -> Option<Vec<(i64, i64)>> {
+    Some(lagoon(input, format)?.vertices)
+}
 
-            (a.0 - b.0).abs() + (a.1 - b.1).abs()
-        })
-        .sum::<i64>();
+/// The trench outline, see [`vertices`]. Lagoons too large to draw, such as the ones from the
+/// color instructions, give `None`.
+pub fn outline(input: &str, format: Format) -> Option<String> {
+    lagoon(input, format)?.render()
+}
 
-    Some(shoelace + perimeter / 2 + 1)
+pub fn part_one(input: &str) -> Option<u64> {
+    u64::try_from(lagoon(input, Format::Plain)?.volume()).ok()
+}
 
-    // let picks = shoelace - perimeter / 2 + 1;
-    // Some(picks + perimeter)
+pub fn part_two(input: &str) -> Option<u64> {
+    u64::try_from(lagoon(input, Format::Color)?.volume()).ok()
 }
 
 #[cfg(test)]
@@ -196,4 +212,45 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(952408144115));
     }
+
+    #[test]
+    fn test_outline() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            outline(&input, Format::Plain).unwrap(),
+            "#######\n\
+             #.....#\n\
+             ###...#\n\
+             ..#...#\n\
+             ..#...#\n\
+             ###.###\n\
+             #...#..\n\
+             ##..###\n\
+             .#....#\n\
+             .######"
+        );
+        assert_eq!(
+            vertices(&input, Format::Color).unwrap()[..3],
+            [(0, 0), (0, 461937), (56407, 461937)]
+        );
+        // far too large to draw
+        assert_eq!(outline(&input, Format::Color), None);
+    }
+
+    #[test]
+    fn test_instruction_forms() {
+        // either form on its own, and a mix of lines
+        assert_eq!(part_one("R 2\nD 2\nL 2\nU 2"), Some(9));
+        assert_eq!(
+            part_two("(#000020)\n(#000021)\n(#000022)\n(#000023)"),
+            Some(9)
+        );
+        assert_eq!(
+            part_two("R 1 (#000020)\n(#000021)\n(#000022)\nU 1 (#000023)"),
+            Some(9)
+        );
+        assert_eq!(part_one("R 1 (#000020)\n(#000021)"), None);
+        // a trench that does not close
+        assert_eq!(part_one("R 2\nD 2\nL 2"), None);
+    }
 }
@@ -1,41 +1,481 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{Display, Write},
+};
 
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag},
-    character::complete::{self, alpha1, newline},
-    multi::{fold_many1, separated_list1},
-    sequence::{delimited, preceded, separated_pair},
+    bytes::complete::tag,
+    character::complete::{self, alpha1, newline, one_of},
+    combinator::opt,
+    multi::{many1, separated_list1},
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
     IResult, Parser,
 };
+use rustworkx_core::petgraph::{
+    algo::tarjan_scc,
+    graph::{DiGraph, NodeIndex},
+};
 
 advent_of_code::solution!(19);
 
-#[derive(Clone, Copy, Debug)]
+const RATINGS: [char; 4] = ['x', 'm', 'a', 's'];
+
+/// An inclusive range of values for each of the four ratings.
+type Ratings = [(u64, u64); 4];
+
+const PUZZLE_RATINGS: Ratings = [(1, 4000); 4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Condition {
     LessThan,
     GreaterThan,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Rule<'a> {
-    part: usize,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Test {
+    rating: usize,
     condition: Condition,
     value: u64,
-    next: &'a str,
 }
 
-#[derive(Clone, Copy, Debug)]
-enum Workflow<'a> {
+impl Test {
+    fn passes(&self, part: &Xmas) -> bool {
+        let rating = part.0[self.rating];
+        match self.condition {
+            Condition::LessThan => rating < self.value,
+            Condition::GreaterThan => rating > self.value,
+        }
+    }
+
+    /// The ratings passing the test and the ones failing it, each `None` if there are none.
+    fn split(&self, ratings: &Ratings) -> (Option<Ratings>, Option<Ratings>) {
+        let (low, high) = ratings[self.rating];
+        let v = self.value;
+        let (pass, fail) = match self.condition {
+            Condition::LessThan => (
+                (low < v).then(|| (low, high.min(v - 1))),
+                (high >= v).then(|| (low.max(v), high)),
+            ),
+            Condition::GreaterThan => (
+                (high > v).then(|| (low.max(v + 1), high)),
+                (low <= v).then(|| (low, high.min(v))),
+            ),
+        };
+        let with = |range: (u64, u64)| {
+            let mut ratings = *ratings;
+            ratings[self.rating] = range;
+            ratings
+        };
+        (pass.map(with), fail.map(with))
+    }
+}
+
+impl Display for Test {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let condition = match self.condition {
+            Condition::LessThan => '<',
+            Condition::GreaterThan => '>',
+        };
+        write!(f, "{}{condition}{}", RATINGS[self.rating], self.value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target<'a> {
     Accept,
     Reject,
-    Rules(Rule<'a>),
-    Next(&'a str),
+    Workflow(&'a str),
+}
+
+impl Display for Target<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Accept => f.write_str("A"),
+            Target::Reject => f.write_str("R"),
+            Target::Workflow(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A rule sends the part to `target` if it passes the test, or always if there is none.
+#[derive(Clone, Copy, Debug)]
+struct Rule<'a> {
+    test: Option<Test>,
+    target: Target<'a>,
+}
+
+impl Display for Rule<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.test {
+            Some(test) => write!(f, "{test}:{}", self.target),
+            None => write!(f, "{}", self.target),
+        }
+    }
 }
 
 #[derive(Debug)]
 struct Xmas([u64; 4]);
 
+/// The workflows in the order they are listed.
+struct System<'a> {
+    names: Vec<&'a str>,
+    rules: Vec<Vec<Rule<'a>>>,
+    index: HashMap<&'a str, usize>,
+}
+
+impl<'a> System<'a> {
+    fn new(workflows: Vec<(&'a str, Vec<Rule<'a>>)>) -> Self {
+        let (names, rules): (Vec<_>, Vec<_>) = workflows.into_iter().unzip();
+        let index = names.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        System {
+            names,
+            rules,
+            index,
+        }
+    }
+
+    fn targets(&self, workflow: usize) -> impl Iterator<Item = &'a str> + '_ {
+        self.rules[workflow]
+            .iter()
+            .filter_map(|rule| match rule.target {
+                Target::Workflow(name) => Some(name),
+                _ => None,
+            })
+    }
+
+    /// Workflow names that are sent to but never defined, with the workflows sending to them.
+    fn undefined(&self) -> Vec<(&'a str, &'a str)> {
+        (0..self.names.len())
+            .flat_map(|w| {
+                self.targets(w)
+                    .filter(|name| !self.index.contains_key(name))
+                    .map(move |name| (name, self.names[w]))
+            })
+            .collect()
+    }
+
+    /// Groups of workflows that can send parts around in a loop, regardless of their ratings.
+    fn cycles(&self) -> Vec<Vec<&'a str>> {
+        let mut graph: DiGraph<(), ()> = DiGraph::new();
+        for _ in &self.names {
+            graph.add_node(());
+        }
+        for w in 0..self.names.len() {
+            for name in self.targets(w) {
+                if let Some(&next) = self.index.get(name) {
+                    graph.add_edge(NodeIndex::new(w), NodeIndex::new(next), ());
+                }
+            }
+        }
+
+        let mut cycles: Vec<Vec<&str>> = tarjan_scc(&graph)
+            .into_iter()
+            .filter(|scc| scc.len() > 1 || graph.contains_edge(scc[0], scc[0]))
+            .map(|scc| {
+                let mut names: Vec<&str> = scc.iter().map(|n| self.names[n.index()]).collect();
+                names.sort_unstable();
+                names
+            })
+            .collect();
+        cycles.sort_unstable();
+        cycles
+    }
+
+    /// Conditional rules leading to the same place as the fallback rule, with only such rules in
+    /// between.
+    fn same_as_fallback(&self, workflow: usize) -> Vec<usize> {
+        let rules = &self.rules[workflow];
+        let Some(fallback) = rules.last().map(|r| r.target) else {
+            return Vec::new();
+        };
+        (0..rules.len() - 1)
+            .rev()
+            .take_while(|&i| rules[i].test.is_some() && rules[i].target == fallback)
+            .collect()
+    }
+
+    fn run(&self, part: &Xmas) -> Result<bool, CompileError> {
+        let mut seen = BTreeSet::new();
+        let mut workflow = "in";
+        loop {
+            let &w = self.index.get(workflow).ok_or(CompileError::Undefined {
+                name: workflow.to_string(),
+            })?;
+            if !seen.insert(w) {
+                return Err(CompileError::Cycle(workflow.to_string()));
+            }
+            let rule = self.rules[w]
+                .iter()
+                .find(|rule| rule.test.is_none_or(|test| test.passes(part)))
+                .unwrap();
+            match rule.target {
+                Target::Accept => return Ok(true),
+                Target::Reject => return Ok(false),
+                Target::Workflow(next) => workflow = next,
+            }
+        }
+    }
+}
+
+/// A workflow that cannot be followed for some parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CompileError {
+    Undefined {
+        name: String,
+    },
+    /// Some parts come back to this workflow after leaving it.
+    Cycle(String),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Undefined { name } => write!(f, "workflow {name} is not defined"),
+            CompileError::Cycle(name) => write!(f, "parts loop back to workflow {name}"),
+        }
+    }
+}
+
+/// A node of the decision tree. Nodes are shared between identical subtrees, and a test with
+/// the same node on both sides is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Node {
+    Reject,
+    Accept,
+    Split {
+        test: Test,
+        pass: usize,
+        fail: usize,
+    },
+}
+
+const REJECT: usize = 0;
+const ACCEPT: usize = 1;
+
+/// How often a rule was reached while compiling, and how often its test could only go one way.
+#[derive(Debug, Default, Clone, Copy)]
+struct Visits {
+    reached: usize,
+    always_pass: usize,
+    always_fail: usize,
+}
+
+/// Workflows compiled for parts with ratings in `domain`, following every rule with the ratings
+/// that can reach it.
+struct Compilation<'s, 'a> {
+    system: &'s System<'a>,
+    domain: Ratings,
+    nodes: Vec<Node>,
+    interned: HashMap<Node, usize>,
+    root: usize,
+    // Keyed by (workflow, rule)
+    visits: HashMap<(usize, usize), Visits>,
+    errors: Vec<CompileError>,
+    path: Vec<usize>,
+}
+
+impl<'s, 'a> Compilation<'s, 'a> {
+    fn new(system: &'s System<'a>, domain: Ratings) -> Self {
+        let mut compilation = Compilation {
+            system,
+            domain,
+            nodes: vec![Node::Reject, Node::Accept],
+            interned: HashMap::from([(Node::Reject, REJECT), (Node::Accept, ACCEPT)]),
+            root: REJECT,
+            visits: HashMap::new(),
+            errors: Vec::new(),
+            path: Vec::new(),
+        };
+        if domain.iter().all(|(low, high)| low <= high) {
+            compilation.root = compilation.target(Target::Workflow("in"), &domain);
+        }
+        compilation
+    }
+
+    fn intern(&mut self, node: Node) -> usize {
+        if let Node::Split { pass, fail, .. } = node {
+            if pass == fail {
+                return pass;
+            }
+        }
+        *self.interned.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        })
+    }
+
+    // Parts that cannot be followed any further are rejected, with the reason recorded
+    fn target(&mut self, target: Target<'a>, ratings: &Ratings) -> usize {
+        let name = match target {
+            Target::Accept => return ACCEPT,
+            Target::Reject => return REJECT,
+            Target::Workflow(name) => name,
+        };
+        let Some(&workflow) = self.system.index.get(name) else {
+            self.errors.push(CompileError::Undefined {
+                name: name.to_string(),
+            });
+            return REJECT;
+        };
+        if self.path.contains(&workflow) {
+            self.errors.push(CompileError::Cycle(name.to_string()));
+            return REJECT;
+        }
+
+        self.path.push(workflow);
+        let node = self.rules(workflow, 0, ratings);
+        self.path.pop();
+        node
+    }
+
+    fn rules(&mut self, workflow: usize, index: usize, ratings: &Ratings) -> usize {
+        let Some(&rule) = self.system.rules[workflow].get(index) else {
+            // Only a malformed workflow runs out of rules
+            return REJECT;
+        };
+        let visits = self.visits.entry((workflow, index)).or_default();
+        visits.reached += 1;
+
+        let Some(test) = rule.test else {
+            return self.target(rule.target, ratings);
+        };
+        match test.split(ratings) {
+            (Some(pass), None) => {
+                visits.always_pass += 1;
+                self.target(rule.target, &pass)
+            }
+            (None, Some(fail)) => {
+                visits.always_fail += 1;
+                self.rules(workflow, index + 1, &fail)
+            }
+            (Some(pass), Some(fail)) => {
+                let pass = self.target(rule.target, &pass);
+                let fail = self.rules(workflow, index + 1, &fail);
+                self.intern(Node::Split { test, pass, fail })
+            }
+            (None, None) => unreachable!("ratings are never empty"),
+        }
+    }
+
+    fn result(&self) -> Result<&Self, CompileError> {
+        match self.errors.first() {
+            Some(err) => Err(err.clone()),
+            None => Ok(self),
+        }
+    }
+
+    fn accepts(&self, part: &Xmas) -> bool {
+        let mut node = self.root;
+        loop {
+            match self.nodes[node] {
+                Node::Reject => return false,
+                Node::Accept => return true,
+                Node::Split { test, pass, fail } => {
+                    node = if test.passes(part) { pass } else { fail }
+                }
+            }
+        }
+    }
+
+    /// Number of accepted rating combinations in the domain, or `None` if it does not fit in a
+    /// `u128`.
+    fn accepted(&self) -> Option<u128> {
+        self.count(self.root, &self.domain)
+    }
+
+    fn count(&self, node: usize, ratings: &Ratings) -> Option<u128> {
+        match self.nodes[node] {
+            Node::Reject => Some(0),
+            Node::Accept => ratings.iter().try_fold(1_u128, |count, (low, high)| {
+                count.checked_mul((high - low) as u128 + 1)
+            }),
+            Node::Split { test, pass, fail } => {
+                let (pass_ratings, fail_ratings) = test.split(ratings);
+                let pass_count = match pass_ratings {
+                    Some(r) => self.count(pass, &r)?,
+                    None => 0,
+                };
+                let fail_count = match fail_ratings {
+                    Some(r) => self.count(fail, &r)?,
+                    None => 0,
+                };
+                pass_count.checked_add(fail_count)
+            }
+        }
+    }
+
+    /// The decision tree in Graphviz DOT, with shared nodes drawn once.
+    fn dot(&self) -> String {
+        let mut dot = String::from("digraph workflows {\n");
+        let mut stack = vec![self.root];
+        let mut seen = BTreeSet::new();
+        while let Some(node) = stack.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            match self.nodes[node] {
+                Node::Reject => writeln!(dot, "  n{node} [label=\"R\", shape=box];").unwrap(),
+                Node::Accept => writeln!(dot, "  n{node} [label=\"A\", shape=box];").unwrap(),
+                Node::Split { test, pass, fail } => {
+                    writeln!(dot, "  n{node} [label=\"{test}\"];").unwrap();
+                    writeln!(dot, "  n{node} -> n{pass} [label=\"yes\"];").unwrap();
+                    writeln!(dot, "  n{node} -> n{fail} [label=\"no\"];").unwrap();
+                    stack.extend([fail, pass]);
+                }
+            }
+        }
+        dot.push('}');
+        dot
+    }
+
+    /// Findings of the static analysis, one per line.
+    fn report(&self) -> Vec<String> {
+        let system = self.system;
+        let mut report = Vec::new();
+        for (name, used_by) in system.undefined() {
+            report.push(format!("undefined workflow {name}, used by {used_by}"));
+        }
+        for cycle in system.cycles() {
+            report.push(format!("cycle through {}", cycle.join(", ")));
+        }
+        // Undefined workflows are already listed above
+        let looping: BTreeSet<String> = self
+            .errors
+            .iter()
+            .filter(|err| matches!(err, CompileError::Cycle(_)))
+            .map(|err| err.to_string())
+            .collect();
+        report.extend(looping);
+
+        for (w, name) in system.names.iter().enumerate() {
+            if !self.visits.contains_key(&(w, 0)) {
+                report.push(format!("unreachable workflow {name}"));
+                continue;
+            }
+
+            let same_as_fallback = system.same_as_fallback(w);
+            for (i, rule) in system.rules[w].iter().enumerate() {
+                let location = format!("{name}[{i}] {rule}");
+                let Some(visits) = self.visits.get(&(w, i)) else {
+                    report.push(format!("unreachable rule {location}"));
+                    continue;
+                };
+                if same_as_fallback.contains(&i) {
+                    report.push(format!(
+                        "redundant condition {location}: same target as the fallback"
+                    ));
+                } else if visits.always_pass == visits.reached {
+                    report.push(format!("redundant condition {location}: always true"));
+                } else if visits.always_fail == visits.reached {
+                    report.push(format!("redundant condition {location}: always false"));
+                }
+            }
+        }
+        report
+    }
+}
+
 fn parse_xmas(input: &str) -> IResult<&str, Xmas> {
     let (input, x) = preceded(tag("x="), complete::u64)(input)?;
     let (input, m) = preceded(tag(",m="), complete::u64)(input)?;
@@ -48,153 +488,101 @@ fn parse_xmas_list(input: &str) -> IResult<&str, Vec<Xmas>> {
     separated_list1(newline, delimited(tag("{"), parse_xmas, tag("}")))(input)
 }
 
-fn parse_condition(input: &str) -> IResult<&str, Rule> {
-    let (input, part) = alt((
-        complete::char('x').map(|_| 0),
-        complete::char('m').map(|_| 1),
-        complete::char('a').map(|_| 2),
-        complete::char('s').map(|_| 3),
-    ))(input)?;
-    let (input, condition) = alt((
-        complete::char('<').map(|_| Condition::LessThan),
-        complete::char('>').map(|_| Condition::GreaterThan),
-    ))(input)?;
-    let (input, value) = complete::u64(input)?;
-    let (input, next) = preceded(tag(":"), alpha1)(input)?;
-
-    Ok((
-        input,
-        Rule {
-            part,
-            condition,
-            value,
-            next,
-        },
+fn parse_test(input: &str) -> IResult<&str, Test> {
+    tuple((
+        one_of("xmas").map(|c| RATINGS.iter().position(|&r| r == c).unwrap()),
+        alt((
+            complete::char('<').map(|_| Condition::LessThan),
+            complete::char('>').map(|_| Condition::GreaterThan),
+        )),
+        complete::u64,
     ))
+    .map(|(rating, condition, value)| Test {
+        rating,
+        condition,
+        value,
+    })
+    .parse(input)
 }
 
-fn parse_workflow(input: &str) -> IResult<&str, (&str, Vec<Workflow>)> {
-    let (input, name) = alpha1(input)?;
-    let (input, rules) = delimited(
-        tag("{"),
-        separated_list1(
-            tag(","),
-            alt((
-                complete::char('A').map(|_| Workflow::Accept),
-                complete::char('R').map(|_| Workflow::Reject),
-                parse_condition.map(|c| Workflow::Rules(c)),
-                is_not("AR}").map(|next| Workflow::Next(next)),
-            )),
-        ),
-        tag("}\n"),
-    )(input)?;
-
-    Ok((input, (name, rules)))
+fn parse_rule(input: &str) -> IResult<&str, Rule<'_>> {
+    let (input, test) = opt(terminated(parse_test, tag(":")))(input)?;
+    let (input, target) = alpha1
+        .map(|name| match name {
+            "A" => Target::Accept,
+            "R" => Target::Reject,
+            _ => Target::Workflow(name),
+        })
+        .parse(input)?;
+    Ok((input, Rule { test, target }))
 }
 
-fn parse_workflows(input: &str) -> IResult<&str, HashMap<&str, Vec<Workflow>>> {
-    let (input, mut workflows) = fold_many1(parse_workflow, HashMap::new, |mut acc, (k, v)| {
-        acc.insert(k, v);
-        acc
-    })(input)?;
-    workflows.insert("A", vec![Workflow::Accept]);
-    workflows.insert("R", vec![Workflow::Reject]);
-    Ok((input, workflows))
+fn parse_workflow(input: &str) -> IResult<&str, (&str, Vec<Rule<'_>>)> {
+    terminated(
+        tuple((
+            alpha1,
+            delimited(tag("{"), separated_list1(tag(","), parse_rule), tag("}")),
+        )),
+        newline,
+    )(input)
 }
 
-fn parse_input(input: &str) -> IResult<&str, (HashMap<&str, Vec<Workflow>>, Vec<Xmas>)> {
-    separated_pair(parse_workflows, tag("\n"), parse_xmas_list)(input)
+fn parse_input(input: &str) -> IResult<&str, (System<'_>, Vec<Xmas>)> {
+    separated_pair(
+        many1(parse_workflow).map(System::new),
+        newline,
+        opt(parse_xmas_list).map(Option::unwrap_or_default),
+    )(input)
 }
 
-pub fn part_one(input: &str) -> Option<u64> {
-    let (_, (workflow_rules, xmas_list)) = parse_input(input).unwrap();
+/// Everything the analysis finds wrong or useless in the workflows, for parts with ratings in
+/// `ratings`, one finding per line.
+pub fn analyze(input: &str, ratings: [(u64, u64); 4]) -> String {
+    let (_, (system, _)) = parse_input(input).unwrap();
+    Compilation::new(&system, ratings).report().join("\n")
+}
 
-    Some(
-        xmas_list
-            .iter()
-            .filter_map(|xmas| {
-                let mut current_flow = Workflow::Next("in");
-                while let Workflow::Next(name) = current_flow {
-                    let workflows = workflow_rules.get(&name).unwrap();
-
-                    for w in workflows {
-                        match w {
-                            Workflow::Rules(rule) => {
-                                let pass = match rule.condition {
-                                    Condition::LessThan => xmas.0[rule.part] < rule.value,
-                                    Condition::GreaterThan => xmas.0[rule.part] > rule.value,
-                                };
-
-                                if pass {
-                                    current_flow = Workflow::Next(rule.next);
-                                    break;
-                                }
-                            }
-                            _ => current_flow = *w,
-                        }
-                    }
-                }
+/// The decision tree compiled for parts with ratings in `ratings`, in Graphviz DOT.
+pub fn decision_tree(input: &str, ratings: [(u64, u64); 4]) -> String {
+    let (_, (system, _)) = parse_input(input).unwrap();
+    Compilation::new(&system, ratings).dot()
+}
 
-                match current_flow {
-                    Workflow::Accept => Some(xmas.0.iter().sum::<u64>()),
-                    _ => None,
-                }
-            })
-            .sum(),
-    )
+/// Number of accepted combinations of ratings in `ratings`, or `None` if the workflows do not
+/// compile or the count does not fit in a `u128`.
+pub fn accepted_combinations(input: &str, ratings: [(u64, u64); 4]) -> Option<u128> {
+    let (_, (system, _)) = parse_input(input).unwrap();
+    let compilation = Compilation::new(&system, ratings);
+    compilation.result().ok()?.accepted()
 }
 
-fn process_workflow(
-    ranges: &mut [(u64, u64); 4],
-    workflow: &Workflow,
-    workflow_rules: &HashMap<&str, Vec<Workflow>>,
-) -> u64 {
-    match workflow {
-        Workflow::Accept => ranges.iter().map(|(min, max)| max - min + 1).product(),
-        Workflow::Reject => 0,
-        Workflow::Rules(rule) => {
-            let Rule {
-                part,
-                condition,
-                value,
-                next,
-            } = rule;
-
-            let (left, right) = ranges[*part];
-            let (pass, fail) = match condition {
-                Condition::LessThan => ((left, *value - 1), (*value, right)),
-                Condition::GreaterThan => ((*value + 1, right), (left, *value)),
-            };
-
-            let mut total = 0;
-            if pass.0 <= pass.1 {
-                let mut pass_ranges = ranges.clone();
-                pass_ranges[*part] = pass;
-                total += process_workflow(&mut pass_ranges, &Workflow::Next(next), workflow_rules);
-            }
-            if fail.0 <= fail.1 {
-                ranges[*part] = fail;
-            }
-            total
-        }
-        Workflow::Next(next) => {
-            let rules = workflow_rules.get(next).unwrap();
-            rules
-                .iter()
-                .map(|rule| process_workflow(ranges, rule, workflow_rules))
-                .sum()
+/// Part one by following the workflows one part at a time, as a reference for the compiled tree.
+pub fn part_one_interpreted(input: &str) -> Option<u64> {
+    let (_, (system, parts)) = parse_input(input).unwrap();
+    let mut total = 0;
+    for part in &parts {
+        if system.run(part).ok()? {
+            total += part.0.iter().sum::<u64>();
         }
     }
+    Some(total)
+}
+
+pub fn part_one(input: &str) -> Option<u64> {
+    let (_, (system, parts)) = parse_input(input).unwrap();
+    let compilation = Compilation::new(&system, [(0, u64::MAX); 4]);
+    let compilation = compilation.result().ok()?;
+    Some(
+        parts
+            .iter()
+            .filter(|part| compilation.accepts(part))
+            .map(|part| part.0.iter().sum::<u64>())
+            .sum(),
+    )
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let (_, (workflow_rules, _)) = parse_input(input).unwrap();
-    let mut xmas_ranges: [(u64, u64); 4] = [(1, 4000); 4];
-    Some(process_workflow(
-        &mut xmas_ranges,
-        &Workflow::Next("in"),
-        &workflow_rules,
-    ))
+    u64::try_from(accepted_combinations(input, PUZZLE_RATINGS)?).ok()
 }
 
 #[cfg(test)]
@@ -212,4 +600,86 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(167409079868000));
     }
+
+    #[test]
+    fn test_analyze() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            analyze(&input, PUZZLE_RATINGS),
+            "redundant condition lnx[0] m>1548:A: same target as the fallback\n\
+             redundant condition gd[0] a>3333:R: same target as the fallback"
+        );
+
+        let workflows = "in{x<10:a,x>20:b,c}\n\
+                         a{x<50:A,m>5:R,R}\n\
+                         b{x>10:A,x<5:R,nope}\n\
+                         c{m<5:c,R}\n\
+                         d{A}\n\n";
+        assert_eq!(
+            analyze(workflows, PUZZLE_RATINGS),
+            "undefined workflow nope, used by b\n\
+             cycle through c\n\
+             parts loop back to workflow c\n\
+             redundant condition a[0] x<50:A: always true\n\
+             unreachable rule a[1] m>5:R\n\
+             unreachable rule a[2] R\n\
+             redundant condition b[0] x>10:A: always true\n\
+             unreachable rule b[1] x<5:R\n\
+             unreachable rule b[2] nope\n\
+             unreachable workflow d"
+        );
+        // the loop is only an error for parts that reach it
+        assert_eq!(accepted_combinations(workflows, PUZZLE_RATINGS), None);
+        assert_eq!(
+            accepted_combinations(workflows, [(1, 9), (1, 2), (1, 1), (1, 1)]),
+            Some(18)
+        );
+    }
+
+    #[test]
+    fn test_rating_ranges() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(
+            accepted_combinations(&input, PUZZLE_RATINGS),
+            Some(167409079868000)
+        );
+        // the whole u64 range fits in a u128 count for a single rating
+        assert_eq!(
+            accepted_combinations("in{A}\n\n", [(0, u64::MAX), (1, 1), (1, 1), (1, 1)]),
+            Some(1 << 64)
+        );
+        assert_eq!(
+            accepted_combinations("in{A}\n\n", [(0, u64::MAX), (1, 1 << 63), (1, 1), (1, 1)]),
+            Some(1 << 127)
+        );
+        // but not the whole range for more than one
+        assert_eq!(accepted_combinations("in{A}\n\n", [(0, u64::MAX); 4]), None);
+        assert_eq!(
+            accepted_combinations("in{x>5:A,m>5:A,A}\n\n", [(0, u64::MAX); 4]),
+            None
+        );
+        assert_eq!(
+            accepted_combinations("in{x>0:A,R}\n\n", [(0, 10), (5, 5), (5, 5), (5, 5)]),
+            Some(10)
+        );
+        assert_eq!(
+            accepted_combinations("in{A}\n\n", [(2, 1), (1, 1), (1, 1), (1, 1)]),
+            Some(0)
+        );
+        assert_eq!(part_one_interpreted(&input), part_one(&input));
+    }
+
+    #[test]
+    fn test_decision_tree() {
+        assert_eq!(
+            decision_tree("in{x<10:a,R}\na{m>5:A,A}\n\n", PUZZLE_RATINGS),
+            "digraph workflows {\n  \
+               n2 [label=\"x<10\"];\n  \
+               n2 -> n1 [label=\"yes\"];\n  \
+               n2 -> n0 [label=\"no\"];\n  \
+               n1 [label=\"A\", shape=box];\n  \
+               n0 [label=\"R\", shape=box];\n\
+             }"
+        );
+    }
 }
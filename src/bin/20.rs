@@ -1,243 +1,410 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write,
+};
 
+use advent_of_code::cycle::{self, Arrivals};
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{self, alpha1, newline},
-    multi::{fold_many1, separated_list1},
-    sequence::{delimited, preceded},
+    multi::{many1, separated_list1},
+    sequence::{delimited, pair, preceded},
     IResult, Parser,
 };
 
 advent_of_code::solution!(20);
 
-#[derive(Debug, Clone)]
-struct Conjunction {
-    // Tracks name of module and it's index bit position
-    inputs: HashMap<String, usize>,
-    // Tracks the pulse strengths of the inputs
-    state: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Button,
+    Broadcaster,
+    FlipFlop,
+    Conjunction,
+    // Only ever receives pulses, like `output` or `rx`
+    Untyped,
 }
 
-#[derive(Debug, Clone)]
-enum Module {
-    FlipFlop(bool),
-    Conjunction(Conjunction),
-    Broadcast,
+const BUTTON: usize = 0;
+
+/// The modules and their wiring, with the button as module 0.
+struct Circuit<'a> {
+    names: Vec<&'a str>,
+    index: HashMap<&'a str, usize>,
+    kinds: Vec<Kind>,
+    destinations: Vec<Vec<usize>>,
+    inputs: Vec<Vec<usize>>,
+    // For each destination, which of its inputs the module is
+    slots: Vec<Vec<usize>>,
 }
 
-#[derive(Debug, Clone)]
-struct Path<'a> {
-    module: Module,
-    destinations: Vec<&'a str>,
+/// What the modules remember between button presses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    on: Vec<bool>,
+    // The last pulse a conjunction got from each input
+    memory: Vec<Vec<bool>>,
 }
 
-struct Pulse<'a> {
-    source: &'a str,
-    destination: &'a str,
-    strength: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pulse {
+    source: usize,
+    destination: usize,
+    high: bool,
+    // Pulses in the chain from the button to this one
+    depth: usize,
 }
 
-enum ReturnPart {
-    PartOne(u32, u32),
-    PartTwo(Option<String>),
+/// A part of the circuit behind one output of the broadcaster that only reaches the rest of it
+/// through `output`, which feeds the module in front of the target.
+#[derive(Debug)]
+struct Counter {
+    root: usize,
+    output: usize,
+    modules: Vec<bool>,
 }
 
-fn parse_path(input: &str) -> IResult<&str, (&str, Path)> {
-    let (input, (name, module)) = alt((
-        preceded(complete::char('%'), alpha1).map(|name| (name, Module::FlipFlop(false))),
-        preceded(complete::char('&'), alpha1).map(|name| {
-            (
-                name,
-                Module::Conjunction(Conjunction {
-                    inputs: HashMap::new(),
-                    state: 0,
-                }),
-            )
-        }),
-        tag("broadcaster").map(|name| (name, Module::Broadcast)),
-    ))(input)?;
-    let (input, destinations) =
-        delimited(tag(" -> "), separated_list1(tag(", "), alpha1), newline)(input)?;
-
-    Ok((
-        input,
-        (
-            name,
-            Path {
-                module,
-                destinations,
-            },
-        ),
-    ))
+/// The depths within a press between which a counter's output stays high: from its latest first
+/// high pulse to the feeder over all presses it fires on, to its earliest low pulse after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    high: usize,
+    reset: usize,
 }
 
-fn parse_input(input: &str) -> IResult<&str, HashMap<&str, Path>> {
-    let (input, mut module_paths) =
-        fold_many1(parse_path, HashMap::new, |mut acc, (name, module)| {
-            acc.insert(name, module);
-            acc
-        })(input)?;
-
-    for (n, p) in module_paths.clone().iter() {
-        for d in p.destinations.iter() {
-            if let Some(Path {
-                module: Module::Conjunction(Conjunction { ref mut inputs, .. }),
-                ..
-            }) = module_paths.get_mut(d)
-            {
-                inputs.insert(String::from(*n), inputs.len());
-            }
+/// The first press on which every counter fires.
+fn first_common(firings: &[Arrivals]) -> Option<u64> {
+    firings
+        .iter()
+        .cloned()
+        .reduce(|a, b| a.intersect(&b))?
+        .first()
+}
+
+impl<'a> Circuit<'a> {
+    fn new(definitions: Vec<((Kind, &'a str), Vec<&'a str>)>) -> Self {
+        let mut circuit = Circuit {
+            names: vec!["button"],
+            index: HashMap::from([("button", BUTTON)]),
+            kinds: vec![Kind::Button],
+            destinations: vec![Vec::new()],
+            inputs: vec![Vec::new()],
+            slots: vec![Vec::new()],
+        };
+        for ((kind, name), _) in &definitions {
+            circuit.module(name);
+            circuit.kinds[circuit.index[name]] = *kind;
         }
+
+        let mut wiring = vec![(BUTTON, "broadcaster")];
+        for ((_, name), destinations) in &definitions {
+            wiring.extend(destinations.iter().map(|d| (circuit.index[name], *d)));
+        }
+        for (source, destination) in wiring {
+            let destination = circuit.module(destination);
+            circuit.slots[source].push(circuit.inputs[destination].len());
+            circuit.destinations[source].push(destination);
+            circuit.inputs[destination].push(source);
+        }
+        circuit
     }
 
-    Ok((input, module_paths))
-}
+    fn module(&mut self, name: &'a str) -> usize {
+        if let Some(&index) = self.index.get(name) {
+            return index;
+        }
+        self.names.push(name);
+        self.kinds.push(Kind::Untyped);
+        self.destinations.push(Vec::new());
+        self.inputs.push(Vec::new());
+        self.slots.push(Vec::new());
+        self.index.insert(name, self.names.len() - 1);
+        self.names.len() - 1
+    }
 
-fn press_button(module_paths: &mut HashMap<&str, Path>, part_one: bool) -> ReturnPart {
-    // p1
-    let mut low_pulses = 0;
-    let mut high_pulses = 0;
-    // p2
-    let mut qt_mem: Option<String> = None;
-
-    let start = Pulse {
-        source: "button",
-        destination: "broadcaster",
-        strength: false,
-    };
-    let mut pulse_queue: VecDeque<Pulse> = VecDeque::from([start]);
-
-    while let Some(Pulse {
-        source,
-        destination,
-        strength,
-    }) = pulse_queue.pop_front()
-    {
-        // p1
-        if strength {
-            high_pulses += 1;
-        } else {
-            low_pulses += 1;
+    fn initial_state(&self) -> State {
+        State {
+            on: vec![false; self.names.len()],
+            memory: self.inputs.iter().map(|i| vec![false; i.len()]).collect(),
         }
+    }
 
-        // If module exists then process it, otherwise ignore
-        if let Some(Path {
-            module,
-            destinations,
-        }) = module_paths.get_mut(destination)
-        {
-            // Determines whether to send pulse, and the pulse strength
-            let processed_pulse: Option<bool> = match module {
-                Module::FlipFlop(ref mut signal) => {
-                    if !strength {
-                        *signal = !*signal;
-                        Some(*signal)
-                    } else {
-                        None
-                    }
+    /// Presses the button once, passing every pulse to `observe`. Pulses to modules outside
+    /// `active` are observed but not delivered.
+    fn press(&self, state: &mut State, active: &[bool], mut observe: impl FnMut(&Pulse)) {
+        let mut queue = VecDeque::from([(BUTTON, false, 0)]);
+        while let Some((source, high, depth)) = queue.pop_front() {
+            for (&destination, &slot) in self.destinations[source].iter().zip(&self.slots[source]) {
+                observe(&Pulse {
+                    source,
+                    destination,
+                    high,
+                    depth,
+                });
+                if !active[destination] {
+                    continue;
                 }
-                Module::Conjunction(Conjunction { inputs, state }) => {
-                    // p2
-                    if destination == "qt" && strength {
-                        qt_mem = Some(String::from(source));
-                    }
 
-                    let n_shift = inputs.get(source).unwrap();
-                    *state = (*state & !(1 << n_shift)) | ((strength as u32) << n_shift);
-                    Some(*state != (1 << inputs.len()) - 1)
+                let send = match self.kinds[destination] {
+                    Kind::Broadcaster => Some(high),
+                    Kind::FlipFlop if !high => {
+                        state.on[destination] = !state.on[destination];
+                        Some(state.on[destination])
+                    }
+                    Kind::Conjunction => {
+                        state.memory[destination][slot] = high;
+                        Some(!state.memory[destination].iter().all(|&m| m))
+                    }
+                    _ => None,
+                };
+                if let Some(send) = send {
+                    queue.push_back((destination, send, depth + 1));
                 }
-                Module::Broadcast => Some(strength),
-            };
+            }
+        }
+    }
 
-            // Adds next pulses to process
-            if let Some(send_strength) = processed_pulse {
-                for dst in destinations.iter() {
-                    pulse_queue.push_back(Pulse {
-                        source: destination,
-                        destination: dst,
-                        strength: send_strength,
-                    })
+    /// Splits the circuit into counters when the target's only input is a conjunction whose
+    /// inputs each come out of a separate part of the circuit behind one output of the
+    /// broadcaster. The target then gets a low pulse on a press every counter fires on, as long as
+    /// all their high pulses reach the conjunction before any of them is reset, which
+    /// [`Circuit::first_press`] checks with each counter's [`Window`].
+    fn counters(&self, target: usize) -> Option<(usize, Vec<Counter>)> {
+        let [feeder] = self.inputs[target][..] else {
+            return None;
+        };
+        let broadcaster = *self.index.get("broadcaster")?;
+        if self.kinds[feeder] != Kind::Conjunction {
+            return None;
+        }
+
+        let mut claimed = vec![false; self.names.len()];
+        let mut counters = Vec::new();
+        for &root in &self.destinations[broadcaster] {
+            let mut modules = vec![false; self.names.len()];
+            let mut stack = vec![root];
+            let mut outputs = HashSet::new();
+            while let Some(module) = stack.pop() {
+                if module == feeder || modules[module] {
+                    continue;
+                }
+                if [BUTTON, broadcaster, target].contains(&module) || claimed[module] {
+                    return None;
+                }
+                modules[module] = true;
+                claimed[module] = true;
+                for &next in &self.destinations[module] {
+                    if next == feeder {
+                        outputs.insert(module);
+                    }
+                    stack.push(next);
                 }
             }
+
+            // Nothing may reach into the counter from elsewhere, except the broadcaster
+            let sealed = (0..self.names.len()).filter(|&m| modules[m]).all(|m| {
+                self.inputs[m]
+                    .iter()
+                    .all(|&i| modules[i] || (i == broadcaster && m == root))
+            });
+            let outputs: Vec<usize> = outputs.into_iter().collect();
+            let [output] = outputs[..] else {
+                return None;
+            };
+            if !sealed {
+                return None;
+            }
+            counters.push(Counter {
+                root,
+                output,
+                modules,
+            });
         }
+
+        let mut fed = counters.iter().map(|c| c.output).collect::<Vec<_>>();
+        fed.sort_unstable();
+        let mut inputs = self.inputs[feeder].clone();
+        inputs.sort_unstable();
+        (fed == inputs).then_some((feeder, counters))
     }
 
-    // idk, there's probablt a better way of doing this
-    if part_one {
-        ReturnPart::PartOne(low_pulses, high_pulses)
-    } else {
-        ReturnPart::PartTwo(qt_mem)
+    /// Runs a counter on its own until it repeats, to find the presses on which it sends a high
+    /// pulse out, and when within those presses its output is high. There is no window if some
+    /// press leaves the output high.
+    fn firings(&self, counter: &Counter, feeder: usize) -> (Arrivals, Option<Window>) {
+        let mut active = counter.modules.clone();
+        active[BUTTON] = true;
+        active[self.index["broadcaster"]] = true;
+
+        let mut fired = Vec::new();
+        let mut window = Some(Window {
+            high: 0,
+            reset: usize::MAX,
+        });
+        let mut presses = 0;
+        let shape = cycle::hashed(&self.initial_state(), |state| {
+            let mut state = state.clone();
+            presses += 1;
+            let (mut high, mut reset, mut ends_high) = (None, None, false);
+            self.press(&mut state, &active, |pulse| {
+                if pulse.source != counter.output || pulse.destination != feeder {
+                    return;
+                }
+                if pulse.high {
+                    high.get_or_insert(pulse.depth);
+                } else if high.is_some() {
+                    reset.get_or_insert(pulse.depth);
+                }
+                ends_high = pulse.high;
+            });
+            if let Some(high) = high {
+                fired.push(presses);
+                window = match (window, reset) {
+                    (Some(w), Some(reset)) if !ends_high => Some(Window {
+                        high: w.high.max(high),
+                        reset: w.reset.min(reset),
+                    }),
+                    _ => None,
+                };
+            }
+            state
+        });
+
+        let (once, residues) = fired
+            .into_iter()
+            .partition(|&press| press <= shape.prefix as u64);
+        let arrivals = Arrivals {
+            once,
+            offset: shape.prefix as u64 + 1,
+            period: shape.period as u64,
+            residues,
+        };
+        (arrivals, window)
     }
-}
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, mut module_paths) = parse_input(input).unwrap();
+    /// Presses the whole circuit until the target gets a pulse of the given level, or the
+    /// circuit comes back to an earlier state without it ever doing so.
+    fn simulate(&self, target: usize, high: bool) -> Option<u64> {
+        let active = vec![true; self.names.len()];
+        let mut state = self.initial_state();
+        let mut seen = HashSet::from([state.clone()]);
+        for presses in 1.. {
+            let mut hit = false;
+            self.press(&mut state, &active, |pulse| {
+                hit |= pulse.destination == target && pulse.high == high;
+            });
+            if hit {
+                return Some(presses);
+            }
+            if !seen.insert(state.clone()) {
+                return None;
+            }
+        }
+        unreachable!()
+    }
 
-    let cycles = 1000;
-    let mut low_pulses = 0;
-    let mut high_pulses = 0;
+    fn first_press(&self, target: usize, high: bool) -> Option<u64> {
+        if !high {
+            if let Some((feeder, counters)) = self.counters(target) {
+                let (firings, windows): (Vec<_>, Vec<_>) =
+                    counters.iter().map(|c| self.firings(c, feeder)).unzip();
+                // Every high pulse has to reach the conjunction before the first reset
+                let windows: Option<Vec<Window>> = windows.into_iter().collect();
+                let lined_up = windows.is_some_and(|windows| {
+                    windows.iter().map(|w| w.high).max() < windows.iter().map(|w| w.reset).min()
+                });
+                if lined_up {
+                    return first_common(&firings);
+                }
+            }
+        }
+        self.simulate(target, high)
+    }
 
-    for _ in 0..cycles {
-        if let ReturnPart::PartOne(low, high) = press_button(&mut module_paths, true) {
-            low_pulses += low;
-            high_pulses += high;
+    /// The circuit in Graphviz DOT, drawing flip-flops as boxes and conjunctions as diamonds.
+    fn dot(&self) -> String {
+        let mut dot = String::from("digraph circuit {\n");
+        for (module, name) in self.names.iter().enumerate() {
+            let shape = match self.kinds[module] {
+                Kind::Button | Kind::Broadcaster => "doublecircle",
+                Kind::FlipFlop => "box",
+                Kind::Conjunction => "diamond",
+                Kind::Untyped => "plaintext",
+            };
+            writeln!(dot, "  {name} [shape={shape}];").unwrap();
+        }
+        for (module, name) in self.names.iter().enumerate() {
+            for &destination in &self.destinations[module] {
+                writeln!(dot, "  {name} -> {};", self.names[destination]).unwrap();
+            }
         }
+        dot.push('}');
+        dot
     }
+}
 
-    Some(low_pulses * high_pulses)
+fn parse_module(input: &str) -> IResult<&str, ((Kind, &str), Vec<&str>)> {
+    pair(
+        alt((
+            preceded(complete::char('%'), alpha1).map(|name| (Kind::FlipFlop, name)),
+            preceded(complete::char('&'), alpha1).map(|name| (Kind::Conjunction, name)),
+            tag("broadcaster").map(|name| (Kind::Broadcaster, name)),
+        )),
+        delimited(tag(" -> "), separated_list1(tag(", "), alpha1), newline),
+    )(input)
 }
 
-// calculates greatest common divisor
-fn gcd(mut a: u64, mut b: u64) -> u64 {
-    while b != 0 {
-        let temp = b;
-        b = a % b;
-        a = temp;
-    }
+fn parse_input(input: &str) -> IResult<&str, Circuit<'_>> {
+    many1(parse_module).map(Circuit::new).parse(input)
+}
 
-    a
+/// The first press on which `target` gets a pulse of the given level, if it ever does.
+pub fn first_press(input: &str, target: &str, high: bool) -> Option<u64> {
+    let (_, circuit) = parse_input(input).unwrap();
+    circuit.first_press(*circuit.index.get(target)?, high)
 }
 
-// calculates least common multiple of the given numbers
-fn lcm(values: Vec<u64>) -> u64 {
-    let mut a = values[0];
-    for b in values.iter().skip(1) {
-        a = a * b / gcd(a, *b)
-    }
-    a
+/// Each counter feeding the module in front of `target`, with the presses on which it fires.
+pub fn counters(input: &str, target: &str) -> Option<String> {
+    let (_, circuit) = parse_input(input).unwrap();
+    let (feeder, counters) = circuit.counters(*circuit.index.get(target)?)?;
+    Some(
+        counters
+            .iter()
+            .map(|c| {
+                format!(
+                    "{} -> {}: {}",
+                    circuit.names[c.root],
+                    circuit.names[c.output],
+                    circuit.firings(c, feeder).0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
 }
 
-pub fn part_two(input: &str) -> Option<u64> {
-    let (_, mut module_paths) = parse_input(input).unwrap();
-    // Starts out as hashmap of strings with value of 0
-    let mut qt_inputs: HashMap<String, u64> = module_paths
-        .get("qt")
-        .map(|Path { module, .. }| match module {
-            Module::Conjunction(conjunction) => {
-                conjunction
-                    .inputs
-                    .keys()
-                    .cloned()
-                    .fold(HashMap::new(), |mut acc, name| {
-                        acc.insert(name, 0_u64);
-                        acc
-                    })
-            }
-            _ => unreachable!(),
-        })
-        .unwrap();
-
-    let mut presses = 0;
-    // Keep pressing until all inouts of `qt` have gotten a high pulse
-    while qt_inputs.values().any(|input_presses| *input_presses == 0) {
-        presses += 1;
-        if let ReturnPart::PartTwo(Some(input_name)) = press_button(&mut module_paths, false) {
-            qt_inputs.insert(input_name, presses);
-        }
+/// The circuit in Graphviz DOT.
+pub fn dot(input: &str) -> String {
+    let (_, circuit) = parse_input(input).unwrap();
+    circuit.dot()
+}
+
+pub fn part_one(input: &str) -> Option<u64> {
+    let (_, circuit) = parse_input(input).unwrap();
+    let active = vec![true; circuit.names.len()];
+    let mut state = circuit.initial_state();
+    let mut pulses = [0, 0];
+    for _ in 0..1000 {
+        circuit.press(&mut state, &active, |pulse| {
+            pulses[pulse.high as usize] += 1
+        });
     }
+    Some(pulses[0] * pulses[1])
+}
 
-    Some(lcm(qt_inputs.values().cloned().collect()))
+pub fn part_two(input: &str) -> Option<u64> {
+    first_press(input, "rx", false)
 }
 
 #[cfg(test)]
@@ -258,5 +425,95 @@ mod tests {
     }
 
     #[test]
-    fn test_part_two() {}
+    fn test_part_two() {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 3);
+        let result = part_two(&input);
+        assert_eq!(result, Some(11 * 13 * 9));
+
+        // the whole circuit agrees, just more slowly
+        let (_, circuit) = parse_input(&input).unwrap();
+        assert_eq!(circuit.simulate(circuit.index["rx"], false), result);
+    }
+
+    #[test]
+    fn test_counters() {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 3);
+        assert_eq!(
+            counters(&input, "rx").unwrap(),
+            "afa -> ainv: every 11 steps from 11\n\
+             bfa -> binv: every 13 steps from 13\n\
+             cfa -> cinv: every 9 steps from 9"
+        );
+        // other targets and levels fall back to running the whole circuit
+        assert_eq!(first_press(&input, "ainv", false), Some(11));
+        assert_eq!(first_press(&input, "feed", true), Some(9));
+        assert_eq!(first_press(&input, "rx", true), Some(1));
+        assert_eq!(first_press(&input, "nowhere", false), None);
+
+        let input = advent_of_code::template::read_file_part("examples", DAY, 2);
+        assert_eq!(counters(&input, "output"), None);
+        assert_eq!(first_press(&input, "output", false), Some(1));
+    }
+
+    #[test]
+    fn test_unaligned_counters() {
+        // `a` stays high from one press to the next, so the conjunction sees both counters high
+        // on press 3 even though they never fire on the same press
+        let input = "broadcaster -> a, b\n%a -> feed\n%b -> bb\n%bb -> feed\n&feed -> rx\n";
+        assert_eq!(
+            counters(input, "rx").unwrap(),
+            "a -> a: every 2 steps from 1\nb -> bb: every 4 steps from 2"
+        );
+        assert_eq!(first_press(input, "rx", false), Some(3));
+
+        let (_, circuit) = parse_input(input).unwrap();
+        let rx = circuit.index["rx"];
+        let (feeder, counters) = circuit.counters(rx).unwrap();
+        assert_eq!(circuit.firings(&counters[0], feeder).1, None);
+        assert_eq!(circuit.simulate(rx, false), Some(3));
+
+        let input = advent_of_code::template::read_file_part("examples", DAY, 3);
+        let (_, circuit) = parse_input(&input).unwrap();
+        let (feeder, counters) = circuit.counters(circuit.index["rx"]).unwrap();
+        assert_eq!(
+            circuit.firings(&counters[0], feeder).1,
+            Some(Window { high: 4, reset: 6 })
+        );
+    }
+
+    #[test]
+    fn test_first_common() {
+        let shifted = Arrivals {
+            once: vec![2],
+            offset: 3,
+            period: 4,
+            residues: vec![3],
+        };
+        let other = Arrivals {
+            once: vec![],
+            offset: 1,
+            period: 6,
+            residues: vec![5],
+        };
+        assert_eq!(shifted.to_string(), "steps 2 and every 4 steps from 3");
+        assert_eq!(first_common(&[shifted, other.clone()]), Some(11));
+
+        let even = Arrivals {
+            once: vec![],
+            offset: 1,
+            period: 4,
+            residues: vec![4],
+        };
+        assert_eq!(first_common(&[even, other]), None);
+    }
+
+    #[test]
+    fn test_dot() {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 2);
+        let dot = dot(&input);
+        assert!(dot.starts_with("digraph circuit {\n  button [shape=doublecircle];\n"));
+        assert!(dot.contains("  con [shape=diamond];\n"));
+        assert!(dot.contains("  output [shape=plaintext];\n"));
+        assert!(dot.contains("  a -> con;\n"));
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::VecDeque;
 
 use nom::{
     bytes::complete::take_till1,
@@ -6,149 +6,315 @@ use nom::{
     multi::separated_list1,
     IResult,
 };
+use num_integer::Integer;
 
 advent_of_code::solution!(21);
 
-#[derive(PartialEq)]
-enum Space {
-    GardenPlot,
-    Rock,
+const STEPS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// How many windows of samples to try before deciding the counts never settle into a quadratic.
+const QUADRATIC_ATTEMPTS: usize = 16;
+
+/// One copy of the garden, which repeats infinitely in every direction for part two.
+struct Garden {
+    rows: usize,
+    cols: usize,
+    plots: Vec<Vec<bool>>,
+    start: (usize, usize),
 }
 
-fn parse_input(input: &str) -> IResult<&str, ((i64, i64), Vec<Vec<Space>>)> {
-    let (input, rows) = separated_list1(newline, take_till1(|c| c == '\n'))(input)?;
-    let mut start = (0, 0);
-    let map = rows
+/// Plots reachable in exactly `steps` steps, given how many plots are first reached after each
+/// number of steps. A plot reached in `d` steps can be returned to every other step after.
+fn count(histogram: &[u64], steps: usize) -> u64 {
+    histogram
         .iter()
-        .enumerate()
-        .map(|(i, row)| {
-            row.as_bytes()
-                .iter()
-                .enumerate()
-                .map(|(j, c)| match c {
-                    b'.' => Space::GardenPlot,
-                    b'#' => Space::Rock,
-                    b'S' => {
-                        start = (i as i64, j as i64);
-                        Space::GardenPlot
-                    }
-                    _ => unreachable!(),
-                })
-                .collect()
-        })
-        .collect();
-    Ok((input, (start, map)))
+        .take(steps + 1)
+        .skip(steps % 2)
+        .step_by(2)
+        .sum()
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    let (_, (start, map)) = parse_input(input).unwrap();
-    let remaining_steps = 64;
-
-    let steps = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-    let mut current_steps: HashSet<(i64, i64)> = HashSet::from([start]);
-    let mut next_steps: HashSet<(i64, i64)> = HashSet::new();
-
-    let rows = map.len() as i64;
-    let cols = map[0].len() as i64;
-
-    for _ in 0..remaining_steps {
-        for (x, y) in current_steps {
-            for (dx, dy) in steps {
-                let (x_new, y_new) = (x + dx, y + dy);
-                if 0 <= x_new
-                    && x_new < rows
-                    && 0 <= y_new
-                    && y_new < cols
-                    && map[x_new as usize][y_new as usize] == Space::GardenPlot
-                {
-                    next_steps.insert((x_new, y_new));
+/// The breadth-first search over the infinite garden, counting how many plots are first reached
+/// after each number of steps. It only runs as far out as it has been asked for so far, growing
+/// its grid of seen plots as needed. Every step moves between the two colours of a checkerboard,
+/// so a grown grid only needs to know the plots reached in the last two steps.
+struct WrappingSearch<'a> {
+    garden: &'a Garden,
+    // Seen plots within `extent` steps of the start in either direction
+    extent: usize,
+    seen: Vec<bool>,
+    // Plots first reached in the last two steps, as offsets from the start
+    previous: Vec<(isize, isize)>,
+    frontier: Vec<(isize, isize)>,
+    histogram: Vec<u64>,
+}
+
+impl<'a> WrappingSearch<'a> {
+    fn new(garden: &'a Garden) -> Self {
+        Self {
+            garden,
+            extent: 0,
+            seen: vec![true],
+            previous: Vec::new(),
+            frontier: vec![(0, 0)],
+            histogram: vec![1],
+        }
+    }
+
+    fn is_plot(&self, (row, col): (isize, isize)) -> bool {
+        let row = (self.garden.start.0 as isize + row).rem_euclid(self.garden.rows as isize);
+        let col = (self.garden.start.1 as isize + col).rem_euclid(self.garden.cols as isize);
+        self.garden.plots[row as usize][col as usize]
+    }
+
+    fn index(&self, (row, col): (isize, isize)) -> usize {
+        let (extent, side) = (self.extent as isize, 2 * self.extent + 1);
+        (row + extent) as usize * side + (col + extent) as usize
+    }
+
+    /// Plots first reached after each number of steps up to `radius`.
+    fn histogram(&mut self, radius: usize) -> &[u64] {
+        if radius > self.extent {
+            self.extent = radius.max(2 * self.extent);
+            let side = 2 * self.extent + 1;
+            self.seen = vec![false; side * side];
+            let recent: Vec<usize> = self
+                .previous
+                .iter()
+                .chain(&self.frontier)
+                .map(|&plot| self.index(plot))
+                .collect();
+            for index in recent {
+                self.seen[index] = true;
+            }
+        }
+
+        while self.histogram.len() <= radius {
+            let mut next = Vec::new();
+            for &(row, col) in &self.frontier {
+                for (dr, dc) in STEPS {
+                    let plot = (row + dr, col + dc);
+                    let index = self.index(plot);
+                    if self.is_plot(plot) && !self.seen[index] {
+                        self.seen[index] = true;
+                        next.push(plot);
+                    }
                 }
             }
+            self.histogram.push(next.len() as u64);
+            self.previous = std::mem::replace(&mut self.frontier, next);
         }
-        current_steps = next_steps.clone();
-        next_steps.clear();
+        &self.histogram[..=radius]
     }
 
-    Some(current_steps.len())
-}
+    /// Once the reachable area is wide enough, the counts `period` steps apart grow
+    /// quadratically. Fits a quadratic to three samples with the same remainder as `steps`,
+    /// moving further out until two more samples confirm it. Every attempt reads its samples from
+    /// the same search, which only runs as far as the current window reaches.
+    fn quadratic(&mut self, steps: usize, period: usize) -> Option<u64> {
+        for attempt in 0..QUADRATIC_ATTEMPTS {
+            let first = steps % period + attempt * period;
+            let last = first + 4 * period;
+            if last >= steps {
+                return Some(count(self.histogram(steps), steps));
+            }
 
-pub fn part_two(input: &str) -> Option<i64> {
-    let (_, (start, map)) = parse_input(input).unwrap();
-    let remaining_steps = 26501365;
+            let histogram = self.histogram(last);
+            let samples: Vec<i128> = (0..5)
+                .map(|k| count(histogram, first + k * period) as i128)
+                .collect();
+            let third_differences =
+                |k: usize| samples[k + 3] - 3 * samples[k + 2] + 3 * samples[k + 1] - samples[k];
+            if third_differences(0) != 0 || third_differences(1) != 0 {
+                continue;
+            }
 
-    let rows = map.len() as i64;
-    let cols = map[0].len() as i64;
+            let k = ((steps - first) / period) as i128;
+            let first_difference = samples[1] - samples[0];
+            let second_difference = samples[2] - 2 * samples[1] + samples[0];
+            let value = samples[0] + k * first_difference + k * (k - 1) / 2 * second_difference;
+            return u64::try_from(value).ok();
+        }
+        None
+    }
+}
 
-    // ty icub3d from yt again!
-    let steps = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-    let mut step_distances: HashMap<(i64, i64), u32> = HashMap::new();
-    let mut frontier: VecDeque<((i64, i64), u32)> = VecDeque::from([(start, 0)]);
-    while let Some((position, distance)) = frontier.pop_front() {
-        if step_distances.contains_key(&position) {
-            continue;
+impl Garden {
+    /// Steps from the start to every plot of a single copy, with `None` for rocks and plots
+    /// that cannot be reached.
+    fn distances(&self) -> Vec<Vec<Option<usize>>> {
+        let mut distances = vec![vec![None; self.cols]; self.rows];
+        distances[self.start.0][self.start.1] = Some(0);
+        let mut queue = VecDeque::from([(self.start, 0)]);
+        while let Some(((row, col), distance)) = queue.pop_front() {
+            for (dr, dc) in STEPS {
+                let (Some(r), Some(c)) = (row.checked_add_signed(dr), col.checked_add_signed(dc))
+                else {
+                    continue;
+                };
+                if r < self.rows && c < self.cols && self.plots[r][c] && distances[r][c].is_none() {
+                    distances[r][c] = Some(distance + 1);
+                    queue.push_back(((r, c), distance + 1));
+                }
+            }
         }
+        distances
+    }
 
-        step_distances.insert(position, distance);
-        for (dx, dy) in steps {
-            let (x_new, y_new) = (position.0 + dx, position.1 + dy);
-            if 0 <= x_new
-                && x_new < rows
-                && 0 <= y_new
-                && y_new < cols
-                && map[x_new as usize][y_new as usize] == Space::GardenPlot
-            {
-                frontier.push_back(((x_new, y_new), distance + 1));
+    fn bounded(&self, steps: usize) -> u64 {
+        let mut histogram = Vec::new();
+        for distance in self.distances().into_iter().flatten().flatten() {
+            if distance >= histogram.len() {
+                histogram.resize(distance + 1, 0);
             }
+            histogram[distance] += 1;
         }
+        count(&histogram, steps)
     }
 
-    let (odd, even, odd_edges, even_edges) = step_distances.iter().fold(
-        (0_usize, 0_usize, 0_usize, 0_usize),
-        |(mut odd, mut even, mut odd_edges, mut even_edges), (_, distance)| {
-            if *distance % 2 == 0 {
-                even += 1;
-                if *distance > 65 {
-                    even_edges += 1;
-                }
+    fn brute_force(&self, steps: usize) -> u64 {
+        count(WrappingSearch::new(self).histogram(steps), steps)
+    }
+
+    /// Whether the garden is an odd square with the start in the middle and no rocks along the
+    /// start's row and column or around the edge, so that every copy is entered from the
+    /// nearest edge midpoint or corner as early as possible.
+    fn has_clear_lanes(&self) -> bool {
+        let size = self.rows;
+        let half = size / 2;
+        self.rows == self.cols
+            && size % 2 == 1
+            && self.start == (half, half)
+            && (0..size).all(|i| {
+                self.plots[half][i]
+                    && self.plots[i][half]
+                    && self.plots[0][i]
+                    && self.plots[size - 1][i]
+                    && self.plots[i][0]
+                    && self.plots[i][size - 1]
+            })
+    }
+
+    /// Counts plots copy by copy for `steps = size / 2 + n * size`, when the reachable area is a
+    /// diamond of whole copies `n` copies out. Neighbouring copies alternate which plots they
+    /// reach, since the size is odd. Copies on the outermost ring miss the corners beyond
+    /// `size / 2` steps, while the ring after it reaches into as many corners of the other
+    /// parity.
+    fn tile_parity(&self, distances: &[Vec<Option<usize>>], steps: usize) -> Option<u64> {
+        let size = self.rows;
+        let half = size / 2;
+        if steps < half || !(steps - half).is_multiple_of(size) {
+            return None;
+        }
+        let n = ((steps - half) / size) as u64;
+
+        let (mut outer, mut inner, mut outer_corners, mut inner_corners) = (0, 0, 0, 0);
+        for &distance in distances.iter().flatten().flatten() {
+            let corner = (distance > half) as u64;
+            if (distance as u64 + n) % 2 == steps as u64 % 2 {
+                outer += 1;
+                outer_corners += corner;
             } else {
-                odd += 1;
-                if *distance > 65 {
-                    odd_edges += 1;
-                }
+                inner += 1;
+                inner_corners += corner;
             }
-            (odd, even, odd_edges, even_edges)
-        },
-    );
+        }
+
+        ((n + 1) * (n + 1) * outer + n * n * inner + n * inner_corners)
+            .checked_sub((n + 1) * outer_corners)
+    }
+
+    /// The copy by copy count, if the garden has clear lanes, `steps` lines up with its edges
+    /// and the count agrees with the brute force over the first two rings of copies. Rocks that
+    /// force detours from the edges make the count drift by a plot or so per ring, which the
+    /// comparison catches.
+    fn tile_decomposition(&self, steps: usize) -> Option<u64> {
+        if !self.has_clear_lanes() {
+            return None;
+        }
+        let distances = self.distances();
+        let (size, half) = (self.rows, self.rows / 2);
+        let mut search = WrappingSearch::new(self);
+        let histogram = search.histogram(half + 2 * size);
+        let agrees = [half + size, half + 2 * size]
+            .iter()
+            .all(|&s| self.tile_parity(&distances, s) == Some(count(histogram, s)));
+        if !agrees {
+            return None;
+        }
+        self.tile_parity(&distances, steps)
+    }
 
-    let grid_steps = (remaining_steps - (rows / 2)) / rows;
-    let total_odd = (grid_steps + 1).pow(2) * odd as i64;
-    let total_even = grid_steps.pow(2) * even as i64;
-    let total_odd_edges = (grid_steps + 1) * odd_edges as i64;
-    let total_even_edges = grid_steps * even_edges as i64;
+    /// Plots reachable in exactly `steps` steps on the infinite garden.
+    fn reachable(&self, steps: usize) -> Option<u64> {
+        if let Some(count) = self.tile_decomposition(steps) {
+            return Some(count);
+        }
+        let period = self.rows.lcm(&self.cols);
+        let mut search = WrappingSearch::new(self);
+        search
+            .quadratic(steps, period)
+            .or_else(|| search.quadratic(steps, 2 * period))
+    }
+}
 
-    Some(total_even + total_odd + total_even_edges - total_odd_edges)
+fn parse_input(input: &str) -> IResult<&str, Garden> {
+    let (input, rows) = separated_list1(newline, take_till1(|c| c == '\n'))(input)?;
+    let mut start = (0, 0);
+    let plots: Vec<Vec<bool>> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.bytes()
+                .enumerate()
+                .map(|(j, c)| match c {
+                    b'.' => true,
+                    b'#' => false,
+                    b'S' => {
+                        start = (i, j);
+                        true
+                    }
+                    _ => unreachable!(),
+                })
+                .collect()
+        })
+        .collect();
+    Ok((
+        input,
+        Garden {
+            rows: plots.len(),
+            cols: plots[0].len(),
+            plots,
+            start,
+        },
+    ))
+}
 
-    // let mut current_steps: HashSet<(i64, i64)> = HashSet::from([start]);
-    // let mut next_steps: HashSet<(i64, i64)> = HashSet::new();
+/// Plots reachable in exactly `steps` steps without leaving the garden.
+pub fn reachable_bounded(input: &str, steps: usize) -> u64 {
+    let (_, garden) = parse_input(input).unwrap();
+    garden.bounded(steps)
+}
 
-    // for _ in 0..remaining_steps {
-    //     for (x, y) in current_steps {
-    //         for (dx, dy) in steps {
-    //             let (x_new, y_new) = (x + dx, y + dy);
+/// Plots reachable in exactly `steps` steps on the infinite garden, or `None` if the counts do
+/// not settle into a pattern that can be extrapolated that far.
+pub fn reachable(input: &str, steps: usize) -> Option<u64> {
+    let (_, garden) = parse_input(input).unwrap();
+    garden.reachable(steps)
+}
 
-    //             let x_map = ((x_new % rows) + rows) % rows;
-    //             let y_map = ((y_new % cols) + cols) % cols;
+/// [`reachable`] by searching every plot within `steps` steps, for checking small step counts.
+pub fn reachable_brute_force(input: &str, steps: usize) -> u64 {
+    let (_, garden) = parse_input(input).unwrap();
+    garden.brute_force(steps)
+}
 
-    //             if map[x_map as usize][y_map as usize] == Space::GardenPlot {
-    //                 next_steps.insert((x_new, y_new));
-    //             }
-    //         }
-    //     }
-    //     current_steps = next_steps.clone();
-    //     next_steps.clear();
-    // }
+pub fn part_one(input: &str) -> Option<u64> {
+    Some(reachable_bounded(input, 64))
+}
 
-    // Some(current_steps.len())
+pub fn part_two(input: &str) -> Option<u64> {
+    reachable(input, 26501365)
 }
 
 #[cfg(test)]
@@ -157,13 +323,65 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(16));
+        // the example takes 6 steps instead of 64
+        let result = reachable_bounded(&advent_of_code::template::read_file("examples", DAY), 6);
+        assert_eq!(result, 16);
     }
 
     #[test]
     fn test_part_two() {
-        let result = part_two(&advent_of_code::template::read_file("examples", DAY));
+        // the example takes 5000 steps instead of 26501365
+        let result = reachable(&advent_of_code::template::read_file("examples", DAY), 5000);
         assert_eq!(result, Some(16733044));
     }
+
+    #[test]
+    fn test_brute_force_oracle() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536), (500, 167004)] {
+            assert_eq!(reachable_brute_force(&input, steps), expected);
+            assert_eq!(reachable(&input, steps), Some(expected));
+        }
+        assert_eq!(reachable(&input, 1000), Some(668697));
+    }
+
+    #[test]
+    fn test_tile_decomposition() {
+        let input = advent_of_code::template::read_file_part("examples", DAY, 2);
+        let (_, garden) = parse_input(&input).unwrap();
+        assert!(garden.has_clear_lanes());
+        // both parities of the number of rings
+        for rings in [3, 4, 7] {
+            let steps = 5 + 11 * rings;
+            assert_eq!(
+                garden.tile_decomposition(steps),
+                Some(garden.brute_force(steps))
+            );
+            assert_eq!(
+                WrappingSearch::new(&garden).quadratic(steps, 11),
+                Some(garden.brute_force(steps))
+            );
+        }
+        // steps that do not line up with the edges need the quadratic
+        assert_eq!(garden.tile_decomposition(100), None);
+        assert_eq!(reachable(&input, 100), Some(garden.brute_force(100)));
+
+        // clear lanes, but plots behind rocks are reached later than the copy by copy count
+        // assumes
+        let detours = "...........\n.#..#....#.\n...#...#...\n.#....#.#..\n...#.......\n\
+                       .....S.....\n..#....#.#.\n.#..#.#....\n...#...#.#.\n.#.......#.\n\
+                       ...........";
+        let (_, garden) = parse_input(detours).unwrap();
+        assert!(garden.has_clear_lanes());
+        assert_eq!(garden.tile_decomposition(5 + 11 * 3), None);
+        assert_eq!(
+            reachable(detours, 5 + 11 * 9),
+            Some(garden.brute_force(5 + 11 * 9))
+        );
+
+        // the example has rocks in the start's row
+        let (_, example) =
+            parse_input(&advent_of_code::template::read_file("examples", DAY)).unwrap();
+        assert!(!example.has_clear_lanes());
+    }
 }
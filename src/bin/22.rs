@@ -55,6 +55,7 @@ fn parse_input(input: &str) -> IResult<&str, Vec<Brick>> {
     )(input)
 }
 
+#[allow(clippy::needless_range_loop)]
 fn stack_bricks(bricks: &[Brick]) -> (Vec<usize>, HashMap<usize, BrickNode>) {
     let mut brick_graph: HashMap<usize, BrickNode> = HashMap::new();
     let mut grid: [[Vec<BrickPiece>; 10]; 10] = Default::default();
@@ -151,7 +152,7 @@ pub fn part_one(input: &str) -> Option<usize> {
     );
 
     let (disintegrated, _) = stack_bricks(&bricks);
-    Some(disintegrated.iter().count())
+    Some(disintegrated.len())
 }
 
 fn calculate_fall(
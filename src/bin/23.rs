@@ -136,7 +136,7 @@ fn map_to_graph(
 
     if open_paths.len() > 2 || (x == map.len() - 1 && y == map[0].len() - 2) {
         let steps_diff = current_steps.abs_diff(prev_int_steps);
-        let to_intersection = graph.entry(current_coord).or_insert(Vec::new());
+        let to_intersection = graph.entry(current_coord).or_default();
         if !to_intersection.contains(&(prev_coord, steps_diff)) {
             to_intersection.push((prev_coord, steps_diff));
         }
@@ -1,4 +1,3 @@
-// use nalgebra::{Matrix2, Matrix2x1, Matrix4, Matrix4x1, RowVector2, RowVector4};
 use nom::{
     bytes::complete::tag,
     character::complete::{self, newline, space1},
@@ -6,6 +5,12 @@ use nom::{
     sequence::{preceded, separated_pair, tuple},
     IResult, Parser,
 };
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::Zero;
+use std::fmt::Display;
+#[cfg(feature = "z3")]
 use z3::{
     ast::{Ast, Int},
     Config, Context, SatResult, Solver,
@@ -15,16 +20,16 @@ advent_of_code::solution!(24);
 
 #[derive(Debug, Clone)]
 struct Position {
-    px: f64,
-    py: f64,
-    pz: f64,
+    px: i64,
+    py: i64,
+    pz: i64,
 }
 
 #[derive(Debug, Clone)]
 struct Velocity {
-    vx: f64,
-    vy: f64,
-    vz: f64,
+    vx: i64,
+    vy: i64,
+    vz: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -33,14 +38,25 @@ struct Hailstone {
     velocity: Velocity,
 }
 
-// const PRECISION: u32 = 32;
+/// Bounds that a crossing point must lie within, on every checked axis.
+#[derive(Debug, Clone, Copy)]
+struct TestArea {
+    lower: i64,
+    upper: i64,
+    include_z: bool,
+}
+
+const TEST_AREA: TestArea = TestArea {
+    lower: 200_000_000_000_000,
+    upper: 400_000_000_000_000,
+    include_z: false,
+};
 
-fn parse_measurements(input: &str) -> IResult<&str, (f64, f64, f64)> {
+fn parse_measurements(input: &str) -> IResult<&str, (i64, i64, i64)> {
     let (input, x) = complete::i64(input)?;
     let (input, y) = preceded(tuple((tag(","), space1)), complete::i64)(input)?;
     let (input, z) = preceded(tuple((tag(","), space1)), complete::i64)(input)?;
-    // Ok((input, (Float::with_val(PRECISION, x), Float::with_val(PRECISION, y), Float::with_val(PRECISION, z))))
-    Ok((input, (x as f64, y as f64, z as f64)))
+    Ok((input, (x, y, z)))
 }
 
 fn parse_hailstone(input: &str) -> IResult<&str, (Position, Velocity)> {
@@ -58,129 +74,410 @@ fn parse_input(input: &str) -> IResult<&str, Vec<Hailstone>> {
     )(input)
 }
 
-fn check_collision(
-    hs_a: &Hailstone,
-    hs_b: &Hailstone,
-    lower_bound: &f64,
-    upper_bound: &f64,
-) -> bool {
-    let Hailstone {
-        position: p_a,
-        velocity: v_a,
-    } = hs_a;
-    let Hailstone {
-        position: p_b,
-        velocity: v_b,
-    } = hs_b;
-
-    let m_a = v_a.vy / v_a.vx;
-    // let m_a = Float::with_val(PRECISION, &v_a.vy / &v_a.vx);
-    let m_b = v_b.vy / v_b.vx;
-    // let m_b = Float::with_val(PRECISION, &v_b.vy / &v_b.vx);
-
-    if m_a == m_b {
-        return false;
+type Vec3 = [i128; 3];
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Vec3, b: Vec3) -> i128 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn ratio(numer: i128, denom: i128) -> BigRational {
+    BigRational::new(numer.into(), denom.into())
+}
+
+impl TestArea {
+    fn axes(&self) -> usize {
+        if self.include_z {
+            3
+        } else {
+            2
+        }
+    }
+
+    // Position and velocity of a hailstone, flattened onto the xy plane unless z is checked
+    fn ray(&self, hailstone: &Hailstone) -> (Vec3, Vec3) {
+        let Hailstone { position, velocity } = hailstone;
+        let z = self.include_z as i128;
+        (
+            [
+                position.px as i128,
+                position.py as i128,
+                position.pz as i128 * z,
+            ],
+            [
+                velocity.vx as i128,
+                velocity.vy as i128,
+                velocity.vz as i128 * z,
+            ],
+        )
     }
 
-    let b_a = p_a.py - m_a * p_a.px;
-    // let b_a = Float::with_val(PRECISION, &p_a.py - Float::with_val(PRECISION, &m_a * &p_a.px));
-    let b_b = p_b.py - m_b * p_b.px;
-    // let b_b = Float::with_val(PRECISION, &p_b.py - Float::with_val(PRECISION, &m_b * &p_b.px));
+    // Whether `p + v * numer / denom` lies within the area, for a positive `denom`
+    fn contains(&self, p: Vec3, v: Vec3, numer: i128, denom: i128) -> bool {
+        let (numer, denom) = (BigInt::from(numer), BigInt::from(denom));
+        let (lower, upper) = (&denom * self.lower, &denom * self.upper);
+        (0..self.axes()).all(|axis| {
+            let coord = &denom * p[axis] + &numer * v[axis];
+            lower <= coord && coord <= upper
+        })
+    }
+}
 
-    let x = (b_b - b_a) / (m_a - m_b);
-    // let x = Float::with_val(PRECISION, Float::with_val(PRECISION, &b_b - &b_a) / Float::with_val(PRECISION, &m_a - &m_b));
-    let y = m_a * x + b_a;
-    // let y = Float::with_val(PRECISION, Float::with_val(PRECISION, &m_a * &x) + &b_a);
+// Rays on the same line: intersects both rays and the test area as intervals of λ along the
+// line `p_a + λ u`
+fn check_overlap((p_a, v_a): (Vec3, Vec3), (p_b, v_b): (Vec3, Vec3), test_area: &TestArea) -> bool {
+    let u = match [v_a, v_b].into_iter().find(|v| *v != [0; 3]) {
+        Some(u) => u,
+        None => return p_a == p_b && test_area.contains(p_a, v_a, 0, 1),
+    };
+    let uu = dot(u, u);
+
+    let mut lower: Option<BigRational> = None;
+    let mut upper: Option<BigRational> = None;
+    let mut clamp = |lo: Option<BigRational>, hi: Option<BigRational>| {
+        if let Some(lo) = lo {
+            lower = Some(lower.take().map_or(lo.clone(), |l| l.max(lo)));
+        }
+        if let Some(hi) = hi {
+            upper = Some(upper.take().map_or(hi.clone(), |u| u.min(hi)));
+        }
+    };
+
+    for (p, v) in [(p_a, v_a), (p_b, v_b)] {
+        let start = ratio(dot(sub(p, p_a), u), uu);
+        match dot(v, u).signum() {
+            1 => clamp(Some(start), None),
+            -1 => clamp(None, Some(start)),
+            _ => clamp(Some(start.clone()), Some(start)),
+        }
+    }
 
-    let t_a = (x - p_a.px) / v_a.vx;
-    // let t_a = Float::with_val(PRECISION, Float::with_val(PRECISION, &x - &p_a.px) / &v_a.vx);
-    let t_b = (x - p_b.px) / v_b.vx;
-    // let t_b = Float::with_val(PRECISION, Float::with_val(PRECISION, &x - &p_b.px) / &v_b.vx);
+    for axis in 0..test_area.axes() {
+        let (lo, hi) = (test_area.lower as i128, test_area.upper as i128);
+        if u[axis] == 0 {
+            if !(lo..=hi).contains(&p_a[axis]) {
+                return false;
+            }
+            continue;
+        }
+        let (a, b) = (
+            ratio(lo - p_a[axis], u[axis]),
+            ratio(hi - p_a[axis], u[axis]),
+        );
+        clamp(Some(a.clone().min(b.clone())), Some(a.max(b)));
+    }
 
-    (t_a >= 0.0 && t_b >= 0.0)
-        && (lower_bound <= &x && &x <= upper_bound)
-        && (lower_bound <= &y && &y <= upper_bound)
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => lower <= upper,
+        _ => true,
+    }
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let (_, hailstones) = parse_input(input).unwrap();
+fn check_collision(hs_a: &Hailstone, hs_b: &Hailstone, test_area: &TestArea) -> bool {
+    let (p_a, v_a) = test_area.ray(hs_a);
+    let (p_b, v_b) = test_area.ray(hs_b);
+    let w = sub(p_b, p_a);
+    let c = cross(v_a, v_b);
 
-    // let lower_bound = Float::with_val(PRECISION, 200000000000000.0);
-    // let upper_bound = Float::with_val(PRECISION, 400000000000000.0);
-    let lower_bound = 200000000000000.0;
-    let upper_bound = 400000000000000.0;
-    // let lower_bound = 7.0;
-    // let upper_bound = 27.0;
+    if c == [0; 3] {
+        let collinear = cross(w, v_a) == [0; 3] && cross(w, v_b) == [0; 3];
+        return collinear && check_overlap((p_a, v_a), (p_b, v_b), test_area);
+    }
+    // Skew lines never cross
+    if dot(w, c) != 0 {
+        return false;
+    }
 
+    let cc = dot(c, c);
+    let t_a = dot(cross(w, v_b), c);
+    let t_b = dot(cross(w, v_a), c);
+    t_a >= 0 && t_b >= 0 && test_area.contains(p_a, v_a, t_a, cc)
+}
+
+fn count_collisions(hailstones: &[Hailstone], test_area: &TestArea) -> u32 {
     let mut collisions = 0;
     for i in 0..hailstones.len() {
         for j in (i + 1)..hailstones.len() {
-            if check_collision(&hailstones[i], &hailstones[j], &lower_bound, &upper_bound) {
+            if check_collision(&hailstones[i], &hailstones[j], test_area) {
                 collisions += 1;
             }
         }
     }
-    Some(collisions)
+    collisions
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    let (_, hailstones) = parse_input(input).unwrap();
+    Some(count_collisions(&hailstones, &TEST_AREA))
 }
 
-// // Gives the row of A matrix for x, y, vx, vy, and b
-// fn xy_row(hs_0: &Hailstone, hs_1: &Hailstone) -> (RowVector4<f64>, f64) {
-//     let Hailstone {
-//         position: Position {
-//             px: px_0, py: py_0, ..
-//         },
-//         velocity: Velocity {
-//             vx: vx_0, vy: vy_0, ..
-//         },
-//     } = hs_0;
-//     let Hailstone {
-//         position: Position {
-//             px: px_1, py: py_1, ..
-//         },
-//         velocity: Velocity {
-//             vx: vx_1, vy: vy_1, ..
-//         },
-//     } = hs_1;
-//     (
-//         RowVector4::new(vy_1 - vy_0, vx_0 - vx_1, py_0 - py_1, px_1 - px_0),
-//         (*vx_0 as i128 * *py_0 as i128 - *vx_1 as i128 * *py_1 as i128
-//             + *px_1 as i128 * *vy_1 as i128
-//             - *px_0 as i128 * *vy_0 as i128) as f64,
-//     )
-// }
-
-// // Gives the row of A matrix for z and vz, and b
-// fn z_row(hs_0: &Hailstone, hs_1: &Hailstone, x_pos: f64, x_vel: f64) -> (RowVector2<f64>, f64) {
-//     let Hailstone {
-//         position: Position {
-//             px: px_0, pz: pz_0, ..
-//         },
-//         velocity: Velocity {
-//             vx: vx_0, vz: vz_0, ..
-//         },
-//     } = hs_0;
-//     let Hailstone {
-//         position: Position {
-//             px: px_1, pz: pz_1, ..
-//         },
-//         velocity: Velocity {
-//             vx: vx_1, vz: vz_1, ..
-//         },
-//     } = hs_1;
-//     (
-//         RowVector2::new(vx_0 - vx_1, px_1 - px_0),
-//         (*vx_0 as i128 * *pz_0 as i128 - *vx_1 as i128 * *pz_1 as i128
-//             + *px_1 as i128 * *vz_1 as i128
-//             - *px_0 as i128 * *vz_0 as i128
-//             - (pz_0 - pz_1) as i128 * x_vel as i128
-//             - (vz_1 - vz_0) as i128 * x_pos as i128) as f64,
-//     )
-// }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
+    Z,
+}
 
-// If error with cland, run: sudo apt-get update && sudo apt-get install libclang-dev -y
+impl Hailstone {
+    fn axis(&self, axis: Axis) -> (i64, i64) {
+        let Hailstone { position, velocity } = self;
+        let (p, v) = match axis {
+            Axis::X => (position.px, velocity.vx),
+            Axis::Y => (position.py, velocity.vy),
+            Axis::Z => (position.pz, velocity.vz),
+        };
+        (p, v)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ThrowError {
+    /// Fewer than five hailstones can never pin down four unknowns per axis pair.
+    TooFewHailstones(usize),
+    /// The pair equations of these axes span fewer than four dimensions.
+    Degenerate { axes: (Axis, Axis), rank: usize },
+    /// The equation from this hailstone pair contradicts the ones before it.
+    Inconsistent {
+        axes: (Axis, Axis),
+        pair: (usize, usize),
+    },
+    /// Two axis pairs solved to different values for the same coordinate.
+    Mismatch(Axis),
+    /// The rock's starting position is not on the integer grid.
+    NonInteger,
+}
+
+impl Display for ThrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrowError::TooFewHailstones(n) => write!(f, "need at least 5 hailstones, got {n}"),
+            ThrowError::Degenerate { axes, rank } => write!(
+                f,
+                "system for {:?}/{:?} is degenerate (rank {rank} of 4)",
+                axes.0, axes.1
+            ),
+            ThrowError::Inconsistent { axes, pair } => write!(
+                f,
+                "hailstones {} and {} are inconsistent with the rest on {:?}/{:?}",
+                pair.0, pair.1, axes.0, axes.1
+            ),
+            ThrowError::Mismatch(axis) => write!(f, "axis pairs disagree on {axis:?}"),
+            ThrowError::NonInteger => f.write_str("rock does not start on an integer position"),
+        }
+    }
+}
+
+// Gives the coefficients of (p_a, p_b, v_a, v_b) followed by the constant for the rock's
+// position/velocity along axes a and b, from equating the rock-specific terms of two hailstones
+// (see `day24_p2_math`)
+fn pair_row(hs_0: &Hailstone, hs_1: &Hailstone, (a, b): (Axis, Axis)) -> [BigInt; 5] {
+    let ((pa_0, va_0), (pb_0, vb_0)) = (hs_0.axis(a), hs_0.axis(b));
+    let ((pa_1, va_1), (pb_1, vb_1)) = (hs_1.axis(a), hs_1.axis(b));
+    let prod = |x: i64, y: i64| BigInt::from(x as i128 * y as i128);
+    [
+        BigInt::from(vb_1 - vb_0),
+        BigInt::from(va_0 - va_1),
+        BigInt::from(pb_0 - pb_1),
+        BigInt::from(pa_1 - pa_0),
+        prod(va_0, pb_0) - prod(va_1, pb_1) + prod(pa_1, vb_1) - prod(pa_0, vb_0),
+    ]
+}
+
+// Incrementally row-reduced integer system. Each kept row has a distinct pivot column and zeros in
+// the pivot columns of the rows kept before it.
+#[derive(Default)]
+struct Echelon {
+    rows: Vec<(usize, [BigInt; 5])>,
+}
+
+enum Reduced {
+    Independent,
+    Dependent,
+    Contradiction,
+}
+
+impl Echelon {
+    fn insert(&mut self, mut row: [BigInt; 5]) -> Reduced {
+        for (pivot, basis) in &self.rows {
+            if row[*pivot].is_zero() {
+                continue;
+            }
+            let (f, g) = (row[*pivot].clone(), basis[*pivot].clone());
+            for (value, b) in row.iter_mut().zip(basis) {
+                *value = &*value * &g - b * &f;
+            }
+            let divisor = row.iter().fold(BigInt::zero(), |acc, v| acc.gcd(v));
+            if !divisor.is_zero() {
+                row.iter_mut().for_each(|v| *v /= &divisor);
+            }
+        }
+
+        match row[..4].iter().position(|v| !v.is_zero()) {
+            Some(pivot) => {
+                self.rows.push((pivot, row));
+                Reduced::Independent
+            }
+            None if row[4].is_zero() => Reduced::Dependent,
+            None => Reduced::Contradiction,
+        }
+    }
+
+    // Gaussian elimination over the rationals on the kept rows, once they reach full rank
+    fn solve(&self) -> Option<[BigRational; 4]> {
+        if self.rows.len() < 4 {
+            return None;
+        }
+        let mut rows: Vec<[BigRational; 5]> = self
+            .rows
+            .iter()
+            .map(|(_, row)| row.clone().map(BigRational::from_integer))
+            .collect();
+
+        for col in 0..4 {
+            let pivot = (col..rows.len()).find(|&r| !rows[r][col].is_zero())?;
+            rows.swap(col, pivot);
+
+            let pivot_row = rows[col].clone();
+            for (r, row) in rows.iter_mut().enumerate() {
+                if r == col || row[col].is_zero() {
+                    continue;
+                }
+                let factor = &row[col] / &pivot_row[col];
+                for (value, pivot) in row.iter_mut().zip(&pivot_row).skip(col) {
+                    *value -= &factor * pivot;
+                }
+            }
+        }
+
+        let mut solution: [BigRational; 4] = Default::default();
+        for (i, value) in solution.iter_mut().enumerate() {
+            *value = &rows[i][4] / &rows[i][i];
+        }
+        Some(solution)
+    }
+}
+
+// Solves the rock's (p_a, p_b, v_a, v_b) from the equations of every hailstone pair, checking that
+// all of them agree. Rows are reduced until the system reaches full rank, after which the remaining
+// rows only need to be checked against the solution.
+fn solve_axes(
+    hailstones: &[Hailstone],
+    axes: (Axis, Axis),
+) -> Result<[BigRational; 4], ThrowError> {
+    let mut echelon = Echelon::default();
+    let mut solution: Option<([BigRational; 4], [BigInt; 4], BigInt)> = None;
+
+    for i in 0..hailstones.len() {
+        for j in (i + 1)..hailstones.len() {
+            let row = pair_row(&hailstones[i], &hailstones[j], axes);
+            let consistent = match &solution {
+                Some((_, numers, denom)) => {
+                    row[..4]
+                        .iter()
+                        .zip(numers)
+                        .map(|(c, n)| c * n)
+                        .sum::<BigInt>()
+                        == &row[4] * denom
+                }
+                None => !matches!(echelon.insert(row), Reduced::Contradiction),
+            };
+            if !consistent {
+                return Err(ThrowError::Inconsistent { axes, pair: (i, j) });
+            }
+
+            if solution.is_none() && echelon.rows.len() == 4 {
+                let solved = echelon.solve().unwrap();
+                let denom = solved
+                    .iter()
+                    .fold(BigInt::from(1), |acc, v| acc.lcm(v.denom()));
+                let numers = solved
+                    .clone()
+                    .map(|v| (v * BigRational::from_integer(denom.clone())).to_integer());
+                solution = Some((solved, numers, denom));
+            }
+        }
+    }
+
+    solution
+        .map(|(solved, ..)| solved)
+        .ok_or(ThrowError::Degenerate {
+            axes,
+            rank: echelon.rows.len(),
+        })
+}
+
+// Exact linear solver: returns the rock's starting position
+fn throw_exact(hailstones: &[Hailstone]) -> Result<[i64; 3], ThrowError> {
+    if hailstones.len() < 5 {
+        return Err(ThrowError::TooFewHailstones(hailstones.len()));
+    }
+
+    let mut position: [Option<BigRational>; 3] = Default::default();
+    let mut first_err = None;
+    for axes in [(Axis::X, Axis::Y), (Axis::X, Axis::Z), (Axis::Y, Axis::Z)] {
+        let solution = match solve_axes(hailstones, axes) {
+            Ok(solution) => solution,
+            Err(err @ ThrowError::Inconsistent { .. }) => return Err(err),
+            Err(err) => {
+                first_err.get_or_insert(err);
+                continue;
+            }
+        };
+        for (axis, value) in [(axes.0, &solution[0]), (axes.1, &solution[1])] {
+            match &position[axis as usize] {
+                Some(known) if known != value => return Err(ThrowError::Mismatch(axis)),
+                Some(_) => {}
+                None => position[axis as usize] = Some(value.clone()),
+            }
+        }
+    }
+
+    let mut rock = [0; 3];
+    for (value, solved) in rock.iter_mut().zip(position) {
+        let Some(solved) = solved else {
+            return Err(first_err.unwrap());
+        };
+        if !solved.is_integer() {
+            return Err(ThrowError::NonInteger);
+        }
+        *value = i64::try_from(solved.to_integer()).map_err(|_| ThrowError::NonInteger)?;
+    }
+    Ok(rock)
+}
+
+/// The rock's starting position from the exact solver, or why the hailstones do not pin down a
+/// single integer throw.
+pub fn rock_throw(input: &str) -> Result<[i64; 3], String> {
+    let (_, hailstones) = parse_input(input).unwrap();
+    throw_exact(&hailstones).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "z3"))]
+pub fn part_two(input: &str) -> Option<i64> {
+    Some(rock_throw(input).ok()?.iter().sum())
+}
+
+/// Hands the first three hailstones to z3 instead of the exact solver.
+#[cfg(feature = "z3")]
 pub fn part_two(input: &str) -> Option<i64> {
     let (_, hailstones) = parse_input(input).unwrap();
+    throw_z3(&hailstones).map(|rock| rock.iter().sum())
+}
 
+// If error with cland, run: sudo apt-get update && sudo apt-get install libclang-dev -y
+#[cfg(feature = "z3")]
+fn throw_z3(hailstones: &[Hailstone]) -> Option<[i64; 3]> {
     let cfg = Config::new();
     let ctx = Context::new(&cfg);
     let solver = Solver::new(&ctx);
@@ -194,14 +491,14 @@ pub fn part_two(input: &str) -> Option<i64> {
 
     for (i, Hailstone { position, velocity }) in hailstones.iter().take(3).enumerate() {
         let (px, py, pz) = (
-            Int::from_i64(&ctx, position.px as i64),
-            Int::from_i64(&ctx, position.py as i64),
-            Int::from_i64(&ctx, position.pz as i64),
+            Int::from_i64(&ctx, position.px),
+            Int::from_i64(&ctx, position.py),
+            Int::from_i64(&ctx, position.pz),
         );
         let (vx, vy, vz) = (
-            Int::from_i64(&ctx, velocity.vx as i64),
-            Int::from_i64(&ctx, velocity.vy as i64),
-            Int::from_i64(&ctx, velocity.vz as i64),
+            Int::from_i64(&ctx, velocity.vx),
+            Int::from_i64(&ctx, velocity.vy),
+            Int::from_i64(&ctx, velocity.vz),
         );
 
         let t = Int::new_const(&ctx, format!("t_{}", i));
@@ -222,98 +519,68 @@ pub fn part_two(input: &str) -> Option<i64> {
         let y = model.get_const_interp(&py_t).unwrap().as_i64().unwrap();
         let z = model.get_const_interp(&pz_t).unwrap().as_i64().unwrap();
 
-        Some(x + y + z)
+        Some([x, y, z])
     } else {
         None
     }
-
-    // for (i, row) in a_xy_matrix.row_iter().enumerate() {
-    //     let (a, b, c, d) = (
-    //         Int::from_i64(&ctx, row[0] as i64),
-    //         Int::from_i64(&ctx, row[1] as i64),
-    //         Int::from_i64(&ctx, row[2] as i64),
-    //         Int::from_i64(&ctx, row[3] as i64)
-    //     );
-    //     let equation = &(&a * &px_t + &b * &py_t + &c * &vx_t + &d * &vy_t)._eq(&Int::from_i64(&ctx, b_xy_vec[i] as i64));
-    //     solver.assert(equation);
-    // }
-
-    // match solver.check() {
-    //     SatResult::Unsat => println!("unsat"),
-    //     SatResult::Unknown => println!("unknown"),
-    //     SatResult::Sat => println!("sat"),
-    // }
-
-    // if let Some(model) = solver.get_model() {
-    //     let x = model.eval(&px_t, true).unwrap().as_i64().unwrap();
-    //     let y = model.eval(&py_t, true).unwrap().as_i64().unwrap();
-    //     dbg!(&x);
-    //     dbg!(&y);
-    // }
-
-    // let hs_a = &hailstones[0];
-    // let hs_b = &hailstones[1];
-    // let hs_c = &hailstones[2];
-    // let hs_d = &hailstones[3];
-    // let hs_e = &hailstones[4];
-
-    // let (ab_xy_a, ab_xy_b) = xy_row(hs_a, hs_b);
-    // let (bc_xy_a, bc_xy_b) = xy_row(hs_b, hs_c);
-    // let (cd_xy_a, cd_xy_b) = xy_row(hs_c, hs_d);
-    // let (de_xy_a, de_xy_b) = xy_row(hs_d, hs_e);
-
-    // let a_xy_matrix = Matrix4::from_rows(&[ab_xy_a, bc_xy_a, cd_xy_a, de_xy_a]);
-    // let b_xy_vec = Matrix4x1::from([ab_xy_b, bc_xy_b, cd_xy_b, de_xy_b]);
-
-    // let a_xy_inverse = a_xy_matrix.try_inverse().unwrap();
-    // let x_xy_solution = a_xy_inverse * b_xy_vec;
-    // // let x_xy_solution = a_xy_matrix.lu().solve(&b_xy_vec).unwrap();
-    // // let x_xy_solution = a_xy_matrix.qr().solve(&b_xy_vec).unwrap();
-    // // let x_xy_solution = a_xy_matrix.full_piv_lu().solve(&b_xy_vec).unwrap();
-    // // let x_xy_solution = a_xy_matrix.svd(true, true).solve(&b_xy_vec, 0.0).unwrap();
-    // let (xp, yp, xv, _) = (
-    //     x_xy_solution.x,
-    //     x_xy_solution.y,
-    //     x_xy_solution.z,
-    //     x_xy_solution.w,
-    // );
-
-    // let (ab_z_a, ab_z_b) = z_row(hs_a, hs_b, xp, xv);
-    // let (cd_z_a, cd_z_b) = z_row(hs_c, hs_d, xp, xv);
-
-    // let a_z_matrix = Matrix2::from_rows(&[ab_z_a, cd_z_a]);
-    // let b_z_vec = Matrix2x1::from([ab_z_b, cd_z_b]);
-
-    // let a_z_inverse = a_z_matrix.try_inverse().unwrap();
-    // let x_z_solution = a_z_inverse * b_z_vec;
-    // // let x_z_solution = a_z_matrix.lu().solve(&b_z_vec).unwrap();
-    // // let x_z_solution = a_z_matrix.qr().solve(&b_z_vec).unwrap();
-    // // let x_z_solution = a_z_matrix.full_piv_lu().solve(&b_z_vec).unwrap();
-    // // let x_z_solution = a_z_matrix.svd(true, true).solve(&b_z_vec, 0.0).unwrap();
-    // let (zp, _) = (x_z_solution.x, x_z_solution.y);
-
-    // // 786_617_045_860_269.3 from A inverse : too high
-    // // 786_617_045_860_272.6 from LU decomp with partial pivoting : too high
-    // // 786_617_045_860_268.6 from QR decomp : forgot
-    // // 786_617_045_860_268   from LU with full pivoting : forgot
-    // // 786_579_841_992_401.1 from SVD : went over submission limit to give high/low
-
-    // // 786_617_045_860_267 my actual answer
-
-    // dbg!(&xp);
-    // dbg!(&yp);
-    // dbg!(&zp);
-    // Some(xp + yp + zp)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE_TEST_AREA: TestArea = TestArea {
+        lower: 7,
+        upper: 27,
+        include_z: false,
+    };
+
     #[test]
     fn test_part_one() {
-        let result = part_one(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(2));
+        let (_, hailstones) =
+            parse_input(&advent_of_code::template::read_file("examples", DAY)).unwrap();
+        assert_eq!(count_collisions(&hailstones, &EXAMPLE_TEST_AREA), 2);
+    }
+
+    #[test]
+    fn test_check_collision_edge_cases() {
+        let (_, hailstones) = parse_input(
+            "10, 0, 0 @ 0, 1, 0\n0, 5, 0 @ 1, 0, 0\n0, 0, 0 @ 1, 1, 0\n3, 3, 0 @ -1, -1, 0\n20, 20, 0 @ 1, 1, 0\n0, 1, 0 @ 1, 1, 0",
+        )
+        .unwrap();
+        let area = TestArea {
+            lower: 0,
+            upper: 15,
+            include_z: false,
+        };
+        // vertical ray crossing a horizontal one at (10, 5)
+        assert!(check_collision(&hailstones[0], &hailstones[1], &area));
+        // collinear rays pointing at each other overlap on [0, 3]
+        assert!(check_collision(&hailstones[2], &hailstones[3], &area));
+        // collinear rays whose overlap lies outside the area
+        assert!(!check_collision(&hailstones[3], &hailstones[4], &area));
+        // parallel but not collinear
+        assert!(!check_collision(&hailstones[2], &hailstones[5], &area));
+    }
+
+    #[test]
+    fn test_check_collision_3d() {
+        let (_, hailstones) =
+            parse_input("0, 0, 0 @ 1, 1, 1\n10, 0, 0 @ -1, 1, 1\n10, 0, 1 @ -1, 1, 1").unwrap();
+        let area = TestArea {
+            lower: 0,
+            upper: 15,
+            include_z: true,
+        };
+        // meet at (5, 5, 5)
+        assert!(check_collision(&hailstones[0], &hailstones[1], &area));
+        // skew in 3D, but the xy projections still cross
+        assert!(!check_collision(&hailstones[0], &hailstones[2], &area));
+        let flat = TestArea {
+            include_z: false,
+            ..area
+        };
+        assert!(check_collision(&hailstones[0], &hailstones[2], &flat));
     }
 
     #[test]
@@ -321,4 +588,53 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(47));
     }
+
+    #[test]
+    fn test_throw_exact() {
+        let (_, hailstones) =
+            parse_input(&advent_of_code::template::read_file("examples", DAY)).unwrap();
+        assert_eq!(throw_exact(&hailstones), Ok([24, 13, 10]));
+        assert_eq!(
+            throw_exact(&hailstones[..4]),
+            Err(ThrowError::TooFewHailstones(4))
+        );
+
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let first_four: Vec<&str> = input.lines().take(4).collect();
+        assert_eq!(
+            rock_throw(&first_four.join("\n")),
+            Err("need at least 5 hailstones, got 4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_throw_exact_degenerate() {
+        let (_, hailstones) = parse_input(
+            "0, 0, 0 @ 1, 1, 1\n1, 2, 3 @ 1, 1, 1\n5, 3, 1 @ 1, 1, 1\n7, 0, 2 @ 1, 1, 1\n2, 9, 4 @ 1, 1, 1",
+        )
+        .unwrap();
+        assert!(matches!(
+            throw_exact(&hailstones),
+            Err(ThrowError::Degenerate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_throw_exact_inconsistent() {
+        let mut input = advent_of_code::template::read_file("examples", DAY);
+        input.push_str("0, 0, 0 @ 1, 1, 1");
+        let (_, hailstones) = parse_input(&input).unwrap();
+        assert!(matches!(
+            throw_exact(&hailstones),
+            Err(ThrowError::Inconsistent { .. })
+        ));
+    }
+
+    #[cfg(feature = "z3")]
+    #[test]
+    fn test_throw_z3() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let (_, hailstones) = parse_input(&input).unwrap();
+        assert_eq!(throw_z3(&hailstones), throw_exact(&hailstones).ok());
+    }
 }
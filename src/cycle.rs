@@ -0,0 +1,356 @@
+//! Cycle detection for iterated state machines, i.e. sequences `x0, f(x0), f(f(x0)), ...` over a
+//! finite state space that must eventually repeat.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use num_integer::Integer;
+
+/// Shape of an eventually periodic sequence: the states from index `prefix` on repeat every
+/// `period` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// Returns the smallest index whose state equals the `n`th state.
+    #[must_use]
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.period
+        }
+    }
+}
+
+/// Floyd's tortoise and hare: constant memory, but evaluates `step` roughly three times per state.
+pub fn floyd<T: Clone + PartialEq>(start: &T, mut step: impl FnMut(&T) -> T) -> Cycle {
+    let mut tortoise = step(start);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        let half = step(&hare);
+        hare = step(&half);
+    }
+
+    let mut prefix = 0;
+    tortoise = start.clone();
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    let mut period = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+
+    Cycle { prefix, period }
+}
+
+/// Brent's algorithm: constant memory and fewer `step` evaluations than [`floyd`].
+pub fn brent<T: Clone + PartialEq>(start: &T, mut step: impl FnMut(&T) -> T) -> Cycle {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    let mut prefix = 0;
+    tortoise = start.clone();
+    hare = start.clone();
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    Cycle { prefix, period }
+}
+
+/// Remembers every state seen so far, evaluating `step` once per state.
+pub fn hashed<T: Clone + Hash + Eq>(start: &T, step: impl FnMut(&T) -> T) -> Cycle {
+    hashed_by(start, step, T::clone)
+}
+
+/// Like [`hashed`], but only remembers `fingerprint(state)`, e.g. a hash or a packed encoding.
+/// Two states with the same fingerprint are assumed to be equal.
+pub fn hashed_by<T, K: Hash + Eq>(
+    start: &T,
+    mut step: impl FnMut(&T) -> T,
+    mut fingerprint: impl FnMut(&T) -> K,
+) -> Cycle {
+    let mut seen: HashMap<K, usize> = HashMap::new();
+    seen.insert(fingerprint(start), 0);
+
+    let mut state = step(start);
+    let mut index = 1;
+    loop {
+        if let Some(prefix) = seen.insert(fingerprint(&state), index) {
+            return Cycle {
+                prefix,
+                period: index - prefix,
+            };
+        }
+        state = step(&state);
+        index += 1;
+    }
+}
+
+/// Returns the `n`th state, stepping at most `cycle.prefix + cycle.period` times.
+pub fn nth<T: Clone>(start: &T, mut step: impl FnMut(&T) -> T, n: usize, cycle: &Cycle) -> T {
+    (0..cycle.reduce(n)).fold(start.clone(), |state, _| step(&state))
+}
+
+/// Steps at which an eventually periodic sequence hits some set of states: the steps in `once`,
+/// which all come before `offset`, followed by every `period` steps from each step in `residues`.
+/// The residues lie in `offset..offset + period`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrivals {
+    pub once: Vec<u64>,
+    pub offset: u64,
+    pub period: u64,
+    pub residues: Vec<u64>,
+}
+
+impl fmt::Display for Arrivals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |steps: &[u64]| {
+            steps
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (self.once.is_empty(), self.residues.is_empty()) {
+            (true, true) => f.write_str("no step"),
+            (false, true) => write!(f, "steps {}", join(&self.once)),
+            (true, false) => write!(
+                f,
+                "every {} steps from {}",
+                self.period,
+                join(&self.residues)
+            ),
+            (false, false) => write!(
+                f,
+                "steps {} and every {} steps from {}",
+                join(&self.once),
+                self.period,
+                join(&self.residues)
+            ),
+        }
+    }
+}
+
+/// Returns the smallest `x >= 0` with `x ≡ a (mod m)` and `x ≡ b (mod n)`, and the period
+/// `lcm(m, n)` of the solutions, or `None` if the congruences have no common solution.
+pub fn crt(a: u64, m: u64, b: u64, n: u64) -> Option<(u64, u64)> {
+    let (a, m, b, n) = (a as i128, m as i128, b as i128, n as i128);
+    let gcd = m.extended_gcd(&n);
+    if (b - a) % gcd.gcd != 0 {
+        return None;
+    }
+
+    let lcm = m / gcd.gcd * n;
+    let k = ((b - a) / gcd.gcd * gcd.x).rem_euclid(n / gcd.gcd);
+    Some(((a + m * k).rem_euclid(lcm) as u64, lcm as u64))
+}
+
+impl Arrivals {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.once.is_empty() && self.residues.is_empty()
+    }
+
+    /// The first arrival, which is step 0 if the sequence starts on an arrival.
+    #[must_use]
+    pub fn first(&self) -> Option<u64> {
+        self.once.iter().chain(&self.residues).copied().min()
+    }
+
+    /// The same arrivals, listing the ones before a later `offset` individually.
+    #[must_use]
+    pub fn with_offset(&self, offset: u64) -> Arrivals {
+        let mut once = self.once.clone();
+        let mut residues = Vec::with_capacity(self.residues.len());
+        for &r in &self.residues {
+            let mut step = r;
+            while step < offset {
+                once.push(step);
+                step += self.period;
+            }
+            residues.push(step);
+        }
+        once.sort_unstable();
+        residues.sort_unstable();
+
+        Arrivals {
+            once,
+            offset,
+            period: self.period,
+            residues,
+        }
+    }
+
+    /// The steps in both sets of arrivals.
+    #[must_use]
+    pub fn intersect(&self, other: &Arrivals) -> Arrivals {
+        let offset = self.offset.max(other.offset);
+        let (a, b) = (self.with_offset(offset), other.with_offset(offset));
+
+        let once = a
+            .once
+            .iter()
+            .copied()
+            .filter(|s| b.once.binary_search(s).is_ok())
+            .collect();
+
+        let mut period = a.period.lcm(&b.period);
+        let mut residues = Vec::new();
+        for &r in &a.residues {
+            for &s in &b.residues {
+                if let Some((x, lcm)) = crt(r, a.period, s, b.period) {
+                    period = lcm;
+                    // Every solution from `offset` on is in both
+                    residues.push(offset + (x + lcm - offset % lcm) % lcm);
+                }
+            }
+        }
+        residues.sort_unstable();
+
+        Arrivals {
+            once,
+            offset,
+            period,
+            residues,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::{brent, crt, floyd, hashed, hashed_by, nth, Arrivals, Cycle};
+
+    // 0, 1, 2, 3, 4, 5, 6, 3, 4, 5, 6, ...
+    fn step(x: &u32) -> u32 {
+        if *x == 6 {
+            3
+        } else {
+            x + 1
+        }
+    }
+
+    const EXPECTED: Cycle = Cycle {
+        prefix: 3,
+        period: 4,
+    };
+
+    #[test]
+    fn detectors_agree() {
+        assert_eq!(floyd(&0, step), EXPECTED);
+        assert_eq!(brent(&0, step), EXPECTED);
+        assert_eq!(hashed(&0, step), EXPECTED);
+        assert_eq!(hashed_by(&0, step, |x| x % 7), EXPECTED);
+    }
+
+    #[test]
+    fn pure_cycle() {
+        let cycle = Cycle {
+            prefix: 0,
+            period: 5,
+        };
+        assert_eq!(floyd(&0, |x| (x + 1) % 5), cycle);
+        assert_eq!(brent(&0, |x| (x + 1) % 5), cycle);
+        assert_eq!(hashed(&0, |x| (x + 1) % 5), cycle);
+    }
+
+    #[test]
+    fn reduce_index() {
+        assert_eq!(EXPECTED.reduce(2), 2);
+        assert_eq!(EXPECTED.reduce(7), 3);
+        assert_eq!(EXPECTED.reduce(1_000_000_000), 3 + (1_000_000_000 - 3) % 4);
+    }
+
+    #[test]
+    fn nth_state() {
+        assert_eq!(nth(&0, step, 2, &EXPECTED), 2);
+        assert_eq!(nth(&0, step, 7, &EXPECTED), 3);
+        assert_eq!(nth(&0, step, 1_000_000_001, &EXPECTED), 5);
+    }
+
+    #[test]
+    fn arrivals_intersection() {
+        let a = Arrivals {
+            once: vec![1],
+            offset: 2,
+            period: 4,
+            residues: vec![3, 4],
+        };
+        let b = Arrivals {
+            once: vec![],
+            offset: 0,
+            period: 6,
+            residues: vec![1],
+        };
+        // 1, 7, 13, 19, ... against 1, 3, 4, 7, 8, 11, 12, ...
+        assert_eq!(
+            a.intersect(&b),
+            Arrivals {
+                once: vec![1],
+                offset: 2,
+                period: 12,
+                residues: vec![7],
+            }
+        );
+        assert_eq!(
+            a.with_offset(5),
+            Arrivals {
+                once: vec![1, 3, 4],
+                offset: 5,
+                period: 4,
+                residues: vec![7, 8],
+            }
+        );
+        assert_eq!(
+            a.with_offset(5).to_string(),
+            "steps 1, 3, 4 and every 4 steps from 7, 8"
+        );
+
+        assert_eq!(a.intersect(&b).first(), Some(1));
+        assert_eq!(b.first(), Some(1));
+        let start = Arrivals {
+            once: vec![],
+            offset: 0,
+            period: 2,
+            residues: vec![0],
+        };
+        assert_eq!(start.first(), Some(0));
+        assert_eq!(b.intersect(&start).first(), None);
+    }
+
+    #[test]
+    fn chinese_remainder() {
+        assert_eq!(crt(2, 4, 1, 6), None);
+        assert_eq!(crt(3, 4, 1, 6), Some((7, 12)));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod cycle;
 mod day;
 pub mod template;
 